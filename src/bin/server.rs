@@ -1,7 +1,11 @@
 #[cfg(feature = "server")]
-use rustgl::server::main::Server;
+use rustgl::{server::main::Server, model::world::map::DEFAULT_MAP};
 
+// usage: server [map id]
 fn main() {
     #[cfg(feature = "server")]
-    Server::run((1234, 1235)).unwrap();
+    {
+        let map = std::env::args().nth(1).unwrap_or_else(|| DEFAULT_MAP.to_string());
+        Server::run((1234, 1235), map.as_str()).unwrap();
+    }
 }
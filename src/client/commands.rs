@@ -49,11 +49,19 @@ pub fn execute_client_command(command: &[u8], context: (Protocol, &mut Game)) ->
             SetUDPAddress => drun::<crate::model::player::commands::PlayerDataPayload>(data, context),
             IndicateClientPlayer => drun::<crate::model::player::commands::IndicateClientPlayer>(data, context),
             PlayerDataPayload => drun::<crate::model::player::commands::PlayerDataPayload>(data, context),
+            LobbyStatus => drun::<crate::model::lobby::commands::LobbyStatus>(data, context),
+            MatchCountdown => drun::<crate::model::lobby::commands::MatchCountdown>(data, context),
+            MatchStarted => drun::<crate::model::lobby::commands::MatchStarted>(data, context),
+            MatchEnded => drun::<crate::model::lobby::commands::MatchEnded>(data, context),
 
             // tick matters
             ClearWorld => drun::<crate::model::world::commands::ClearWorld>(data, context),
             RunWorldCommand => drun::<crate::model::world::commands::RunWorldCommand>(data, context),
             FixWorld => drun::<crate::model::world::commands::FixWorld>(data, context),
+            WorldStateHash => drun::<crate::model::world::commands::WorldStateHash>(data, context),
+            VisionChange => drun::<crate::model::world::commands::VisionChange>(data, context),
+            WorldSnapshot => drun::<crate::model::world::commands::WorldSnapshot>(data, context),
+            Handshake => drun::<crate::model::world::commands::Handshake>(data, context),
             _ => {
                 println!("Command ID not implemented on client: {:?}", id);
                 Ok(())
@@ -1,6 +1,6 @@
 use nalgebra::Vector2;
 use ogl33::glViewport;
-use std::{ffi::CStr, str::FromStr, net::SocketAddr, collections::{HashMap, BTreeMap}};
+use std::{ffi::CStr, str::FromStr, net::SocketAddr, collections::{HashMap, HashSet, BTreeMap}};
 use std::ops::Bound::{Included, Unbounded};
 use glfw::{Action, Context, Key};
 use nalgebra::{Vector4, Vector3, Similarity3};
@@ -10,8 +10,8 @@ use crate::{
     client::{chatbox, commands::execute_client_command, camera::{CameraContext, CameraMatrix}},
     model::{world::{
         World,
-        character::{CharacterID, CharacterType}, commands::{GenerateCharacter, ListChar, EnsureCharacter, ClearWorld, WorldCommand, FixWorld}, system::{movement::MoveCharacterRequest, auto_attack::AutoAttackRequest, flash::FlashRequest, collision::CollisionInfo}, logging::Logger, template::WorldTemplate, 
-    }, commands::core::GetAddress, Subscription, PrintError, player::{commands::{PlayerSubs, PlayerSubCommand, PlayerLogIn, PlayerLogOut, ChatMessage, GetPlayerData}, model::{PlayerID, PlayerData, PlayerDataView}}, TICK_RATE, Tick}, networking::{client::ClientUpdate, Protocol},
+        character::{CharacterID, CharacterType, CharacterIDGenerator}, commands::{GenerateCharacter, ListChar, EnsureCharacter, ClearWorld, WorldCommand, UpdateCharacter, WorldSnapshot, apply_vision_change}, replication::ComponentData, system::{movement::MoveCharacterRequest, auto_attack::AutoAttackRequest, flash::FlashRequest, aoe::AoeRequest, bolt::BoltRequest, inventory::{BuyRequest, SellRequest}}, logging::{Logger, DesyncReport}, template::WorldTemplate, definitions::{Definitions, DEFINITIONS_PATH}, map::{MapPackage, DEFAULT_MAP}, 
+    }, lobby::commands::ReadyRequest, commands::core::GetAddress, Subscription, PrintError, player::{commands::{PlayerSubs, PlayerSubCommand, PlayerLogIn, PlayerLogOut, ChatMessage, GetPlayerData}, model::{PlayerID, PlayerData, PlayerDataView, TeamID}}, TICK_RATE, Tick}, networking::{client::ClientUpdate, Protocol},
 };
 
 use crate::networking::client::Client as Connection;
@@ -28,7 +28,8 @@ pub type TickDiff = Tick;
 
 pub enum TickCommand {
     WorldCommand(Tick, u32, WorldCommand),
-    FixWorld(FixWorld),
+    FixWorld(Tick, u32, UpdateCharacter),
+    VisionChange(Tick, u32, Vec<UpdateCharacter>, Vec<CharacterID>),
 }
 
 pub struct Game<'a> {
@@ -55,8 +56,20 @@ pub struct Game<'a> {
     pub tick_commands: HashMap<Tick, Vec<(u32, TickCommand)>>,
     pub tick_count: HashMap<Tick, HashMap<TickDiff, u32>>,
     pub players: PlayerData,
+    pub server_hashes: HashMap<Tick, u64>,
+    pub local_hashes: HashMap<Tick, u64>,
+    pub desync_tick: Option<Tick>,
+    pub world_snapshot: Option<WorldSnapshot>,
+    pub character_id_gen: CharacterIDGenerator, // the server's, as of the last world snapshot
+    pub fix_baselines: HashMap<CharacterID, BTreeMap<Tick, ComponentData>>,
+    pub definitions: Definitions,
+    pub map: MapPackage,
+    pub explored: HashSet<Vector2<i32>>, // fog of war cells our team has seen
 }
 
+// how many ticks of world hashes to keep around while waiting for the other side's hash
+const HASH_HISTORY: Tick = 600;
+
 impl Game<'_> {
     pub fn run(addr: Option<(SocketAddr, SocketAddr)>) {
         let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
@@ -95,7 +108,10 @@ impl Game<'_> {
             .collect();
         let simple_render = graphics::simple::Renderer::new_square();
         let _texture_render = graphics::textured::Renderer::new_square();
-        let temp_world = World::new(CollisionInfo::test_collision());
+        let definitions = Definitions::load(DEFINITIONS_PATH).expect("Failed to load character definitions");
+        // the server may switch to another map once connected
+        let map = MapPackage::load(DEFAULT_MAP).expect("Failed to load the default map");
+        let temp_world = World::new(&map, &definitions);
         let mut game = {
             let ui_scale = 32.0;
             Game {
@@ -132,7 +148,16 @@ impl Game<'_> {
                 tick_base: 0,
                 tick_commands: HashMap::new(),
                 tick_count: HashMap::new(),
-                players: PlayerData { players: HashMap::new() }
+                players: PlayerData { players: HashMap::new(), teams: HashMap::new() },
+                server_hashes: HashMap::new(),
+                local_hashes: HashMap::new(),
+                desync_tick: None,
+                world_snapshot: None,
+                character_id_gen: CharacterIDGenerator::new(),
+                fix_baselines: HashMap::new(),
+                definitions,
+                map,
+                explored: HashSet::new(),
             }
         };
 
@@ -178,6 +203,10 @@ impl Game<'_> {
                 offset: Vector2::new(0.0, -0.19),
                 size: Vector2::new(0.35, 0.41),
             });
+            clickbox_types.insert(CharacterType::Tower, Clickbox {
+                offset: Vector2::new(0.0, -0.5),
+                size: Vector2::new(0.5, 1.0),
+            });
             clickbox_types
         };
 
@@ -196,6 +225,7 @@ impl Game<'_> {
         }
 
         let mut logger = Logger::init("client.log").unwrap();
+        let mut desync_report = DesyncReport::init("desync.log").unwrap();
 
         let mut fpsc: i32 = 0;
         let mut fps: i32 = 0;
@@ -288,6 +318,22 @@ impl Game<'_> {
                 }
             };
             let display_tick = game.tick_base + display_tick_diff;
+            // restart history from the server's snapshot, dropping commands that are already part
+            // of it
+            if let Some(snapshot) = game.world_snapshot.take() {
+                match snapshot.make_world(&World::from(&game.world_template)) {
+                    Ok(world) => {
+                        history_world = world;
+                        game.character_id_gen = snapshot.id_gen.clone();
+                        game.tick_commands.retain(|tick, _| *tick >= snapshot.tick);
+                        game.fix_baselines.clear();
+                        game.local_hashes.clear();
+                        game.desync_tick = None;
+                        game.chatbox.println(format!("Received world snapshot at tick {}", snapshot.tick).as_str());
+                    },
+                    Err(err) => game.chatbox.println(format!("Failed to load world snapshot: {:?}", err).as_str()),
+                }
+            }
             let history_difference = display_tick - history_world.tick;
             if history_difference < 0 {
                 history_world.tick = display_tick;
@@ -314,17 +360,25 @@ impl Game<'_> {
                     if let Some(history) = history {
                         for (_, cmd) in history {
                             match cmd {
-                                TickCommand::FixWorld(FixWorld { update, ordering: _, tick }) => {
+                                TickCommand::FixWorld(tick, _, update) => {
                                     println!("Fix world from tick {}", tick);
-                                    match update.update_character(world) {
+                                    let mut fixed = world.clone();
+                                    match update.update_character(&mut fixed) {
                                         Ok(()) => (),
-                                        Err(err) => world.errors.push(err)
+                                        Err(err) => fixed.errors.push(err)
+                                    }
+                                    let diff = world.diff(&fixed);
+                                    if !diff.is_empty() {
+                                        desync_report.report(world.tick, &update.id, &diff);
                                     }
+                                    *world = fixed;
                                 },
+                                TickCommand::VisionChange(_, _, updated, left) => apply_vision_change(world, &updated, &left),
                                 TickCommand::WorldCommand(_, _, wc) => world_commands.push(wc),
                             }
                         }
                     }
+                    game.local_hashes.insert(world.tick, world.state_hash());
                     *world = world.update(&world_commands, 1.0 / TICK_RATE);
                     // logger.log(world);
                     for error in world.errors.drain(0..world.errors.len()) {
@@ -338,6 +392,7 @@ impl Game<'_> {
                     }
                 }
             }
+            game.check_state_hashes(history_world.tick);
 
             // recalculate display world
             game.world = {
@@ -356,12 +411,13 @@ impl Game<'_> {
                     if let Some(history) = history_commands {
                         for (_, cmd) in history {
                             match cmd {
-                                TickCommand::FixWorld(FixWorld { update, ordering: _, tick: _ }) => {
+                                TickCommand::FixWorld(_, _, update) => {
                                     match update.update_character(&mut world) {
                                         Ok(()) => (),
                                         Err(err) => world.errors.push(err)
                                     }
                                 },
+                                TickCommand::VisionChange(_, _, updated, left) => apply_vision_change(&mut world, updated, left),
                                 TickCommand::WorldCommand(_, _, wc) => world_commands.push(wc.clone()),
                             }
                         }
@@ -627,6 +683,36 @@ impl Game<'_> {
                             }
                         }
                     },
+                    (State::DEFAULT, glfw::WindowEvent::Key(glfw::Key::Q, _, Action::Press, _)) => {
+                        if game.connection.is_connected() {
+                            if let Some(pid) = game.selected_player {
+                                if let Some(player) = game.players.get_player(&pid) {
+                                    if let Some(cid) = player.selected_char {
+                                        let pos = game.mouse_pos_world;
+                                        game.connection.send(Protocol::UDP, &AoeRequest {
+                                            user: cid,
+                                            target_pos: pos,
+                                        }).ok();
+                                    }
+                                }
+                            }
+                        }
+                    },
+                    (State::DEFAULT, glfw::WindowEvent::Key(glfw::Key::W, _, Action::Press, _)) => {
+                        if game.connection.is_connected() {
+                            if let Some(pid) = game.selected_player {
+                                if let Some(player) = game.players.get_player(&pid) {
+                                    if let Some(cid) = player.selected_char {
+                                        let pos = game.mouse_pos_world;
+                                        game.connection.send(Protocol::UDP, &BoltRequest {
+                                            user: cid,
+                                            target_pos: pos,
+                                        }).ok();
+                                    }
+                                }
+                            }
+                        }
+                    },
                     // (State::DEFAULT, glfw::WindowEvent::MouseButton(glfw::MouseButtonLeft, Action::Press, _)) => {
                     //     game.destination = None;
                     //     if game.connection.is_connected() {
@@ -672,6 +758,56 @@ impl Game<'_> {
         }
     }
 
+    // compare the server's world hashes against the hashes of our history world
+    // only the first diverging tick is reported, until the worlds match again
+    pub fn check_state_hashes(&mut self, history_tick: Tick) {
+        let mut ticks: Vec<Tick> = self.server_hashes.keys()
+            .filter(|tick| self.local_hashes.contains_key(tick))
+            .copied()
+            .collect();
+        ticks.sort_unstable();
+        for tick in ticks {
+            if let (Some(server), Some(local)) = (self.server_hashes.remove(&tick), self.local_hashes.remove(&tick)) {
+                match self.desync_tick {
+                    None if server != local => {
+                        self.desync_tick = Some(tick);
+                        self.chatbox.println(format!("World desync detected, first diverged at tick {}", tick).as_str());
+                    },
+                    Some(desync_tick) if server == local && tick > desync_tick => {
+                        self.desync_tick = None;
+                        self.chatbox.println(format!("World back in sync at tick {} (diverged at tick {})", tick, desync_tick).as_str());
+                    },
+                    _ => (),
+                }
+            }
+        }
+        // forget hashes that the other side will never send
+        self.server_hashes.retain(|tick, _| *tick > history_tick - HASH_HISTORY);
+        self.local_hashes.retain(|tick, _| *tick > history_tick - HASH_HISTORY);
+    }
+
+    // the character the logged in player controls
+    fn selected_char(&self) -> Option<CharacterID> {
+        self.selected_player
+            .and_then(|pid| self.players.get_player(&pid))
+            .and_then(|player| player.selected_char)
+    }
+
+    pub fn selected_team(&self) -> Option<TeamID> {
+        self.selected_player
+            .and_then(|pid| self.players.get_player(&pid))
+            .and_then(|player| player.team)
+    }
+
+    // start over with an empty world on another map
+    pub fn set_map(&mut self, map: MapPackage) {
+        let world = World::new(&map, &self.definitions);
+        self.world = world.clone();
+        self.world_template = WorldTemplate { world };
+        self.explored.clear();
+        self.map = map;
+    }
+
     pub fn process_chat(&mut self, command: &str) -> Result<Option<String>, String> {
         if !command.starts_with('/') {
             self.process_chat((String::from("/send ") + command).as_str())
@@ -708,7 +844,7 @@ impl Game<'_> {
                             None
                         }
                     };
-                    self.connection.send(Protocol::TCP, &PlayerLogIn {existing, name})?;
+                    self.connection.send(Protocol::TCP, &PlayerLogIn {existing, name, definitions_hash: self.definitions.hash, map_hash: self.map.hash})?;
                     Ok(None)
                 },
                 ["login", ..] => {
@@ -729,7 +865,7 @@ impl Game<'_> {
                             None
                         }
                     };
-                    self.connection.send(Protocol::TCP, &PlayerLogIn {existing, name})?;
+                    self.connection.send(Protocol::TCP, &PlayerLogIn {existing, name, definitions_hash: self.definitions.hash, map_hash: self.map.hash})?;
                     self.connection.send(Protocol::TCP, &PlayerSubs(PlayerSubCommand::SetSubs(vec![Subscription::Chat, Subscription::World])))?;
                     self.connection.send(Protocol::TCP, &EnsureCharacter)?;
                     Ok(None)
@@ -824,6 +960,41 @@ impl Game<'_> {
                     self.connection.send(Protocol::TCP, &ClearWorld)?;
                     Ok(None)
                 },
+                // until there is a shop ui
+                ["shop"] => {
+                    let user = self.selected_char().ok_or_else(|| "No character selected".to_string())?;
+                    let gold = self.world.gold.components.get(&user).map(|gold| gold.gold).unwrap_or(0.0);
+                    let inventory = self.world.inventory.components.get(&user)
+                        .map(|inventory| inventory.items.iter()
+                            .map(|item| item.clone().unwrap_or_else(|| "-".to_string()))
+                            .collect::<Vec<String>>()
+                            .join(", "))
+                        .unwrap_or_default();
+                    let mut items: Vec<String> = self.world.info.items.iter()
+                        .map(|(name, item)| format!("{} ({})", name, item.cost))
+                        .collect();
+                    items.sort();
+                    Ok(Some(format!("Gold: {}\nInventory: {}\nItems: {}", gold.floor(), inventory, items.join(", "))))
+                },
+                ["buy", item] => {
+                    let user = self.selected_char().ok_or_else(|| "No character selected".to_string())?;
+                    self.connection.send(Protocol::TCP, &BuyRequest { user, item: item.to_string() })?;
+                    Ok(None)
+                },
+                ["sell", slot] => {
+                    let user = self.selected_char().ok_or_else(|| "No character selected".to_string())?;
+                    let slot = slot.parse().map_err(|err| format!("Parse error: {:?}", err))?;
+                    self.connection.send(Protocol::TCP, &SellRequest { user, slot })?;
+                    Ok(None)
+                },
+                ["ready"] => {
+                    self.connection.send(Protocol::TCP, &ReadyRequest(true))?;
+                    Ok(None)
+                },
+                ["unready"] => {
+                    self.connection.send(Protocol::TCP, &ReadyRequest(false))?;
+                    Ok(None)
+                },
                 _ => Err("Unknown command or incorrect parameters.".to_string())
             }
         }
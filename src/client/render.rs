@@ -1,25 +1,52 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::f32::consts::PI;
 use std::ops::Bound::{Included, Unbounded};
-use nalgebra::{Vector2, Vector4, Similarity3, Vector3, Rotation2};
+use nalgebra::{Vector2, Vector4, Similarity3, Vector3, Rotation2, Matrix4};
 use crate::model::TICK_RATE;
 use crate::model::player::model::PlayerDataView;
+use crate::model::world::World;
 use crate::model::world::character::CharacterType;
 use crate::model::world::component::ComponentStorageContainer;
 use crate::model::world::system::auto_attack::AutoAttackFireEvent;
 use crate::model::world::system::base::CharacterFlip;
+use crate::model::world::system::death::is_alive;
+use crate::model::world::system::projectile::ProjectileTarget;
+use crate::model::world::system::team::{are_allies, get_team};
+use crate::model::world::map::MapPackage;
+use crate::model::world::vision;
 use crate::{model::world::character::CharacterID, graphics::{self, TextureOptions}};
 use super::camera::CameraMatrix;
 use super::game::Game;
 
 const SLIGHT_DEPTH_SEPARATION: f32 = 0.0001;
 
+// fog of war is drawn as a grid of squares this many world units wide
+const FOG_CELL_SIZE: f32 = 0.5;
+
 struct MapLayer {
     _width: u32,
     _height: u32,
     _data: Vec<f32>,
     data_texture: graphics::Texture,
     texture: graphics::Texture,
+    tiles: f32,
+}
+
+fn load_map_layers(texture_library: &mut graphics::TextureLibrary, map: &MapPackage) -> Vec<MapLayer> {
+    map.manifest.layers.iter().map(|layer| {
+        let img_obj = image::io::Reader::open(map.dir.join(&layer.mask)).unwrap().decode().unwrap();
+        let img = img_obj.as_rgba8().unwrap();
+        let img_data = img.as_raw();
+        let data: Vec<u8> = img_data.iter().skip(3).step_by(4).copied().collect();
+        MapLayer {
+            _width: img.width(),
+            _height: img.height(),
+            _data: data.iter().map(|pixel| *pixel as f32 / 255.0).collect(),
+            data_texture: texture_library.make_texture_from(img.width(), img.height(), &data, &[TextureOptions::Red, TextureOptions::Bilinear]),
+            texture: texture_library.make_texture(layer.texture.as_str(), &[TextureOptions::Repeating, TextureOptions::Bilinear]),
+            tiles: layer.tiles,
+        }
+    }).collect()
 }
 
 struct Animation {
@@ -64,9 +91,47 @@ pub fn extract_frame(timer: f32, animation_fps: f32, frame_start: usize, frame_c
     }
 }
 
+// health bar, with a mana bar under it for characters that have mana
+// position is in view coordinates
+fn render_bars(simple_render: &graphics::simple::Renderer, proj: &Matrix4<f32>, world: &World, cid: &CharacterID, selected_char: Option<CharacterID>, position: Vector2<f32>, width: f32) {
+    let health = match world.health.components.get(cid) {
+        Some(health) => health,
+        None => return,
+    };
+    let height = 20.0;
+    let matrix_back = graphics::make_matrix(position, Vector2::new(width, height), 0.0);
+    let matrix_health = graphics::make_matrix(
+        position - Vector2::new(width, 0.0) * (1.0 - health.health / health.max_health) / 2.0,
+        Vector2::new(width * health.health / health.max_health, height),
+        0.0);
+    // green for us and our allies, red for enemies, yellow for characters without a team
+    let health_color = if selected_char.map(|sc| sc == *cid || are_allies(world, &sc, cid)).unwrap_or(false) {
+        Vector4::new(0.0, 1.0, 0.0, 1.0)
+    } else if get_team(world, cid).is_some() {
+        Vector4::new(1.0, 0.0, 0.0, 1.0)
+    } else {
+        Vector4::new(1.0, 0.9, 0.0, 1.0)
+    };
+    simple_render.render(&(proj * matrix_back), &Vector4::new(0.2, 0.2, 0.2, 1.0), graphics::VertexRange::Full);
+    simple_render.render(&(proj * matrix_health), &health_color, graphics::VertexRange::Full);
+
+    // mana goes in a thinner bar under the health bar
+    if let Some(mana) = world.mana.components.get(cid).filter(|mana| mana.max_mana > 0.0) {
+        let mana_height = height / 2.0;
+        let mana_position = position + Vector2::new(0.0, (height + mana_height) / 2.0);
+        let matrix_back = graphics::make_matrix(mana_position, Vector2::new(width, mana_height), 0.0);
+        let matrix_blue = graphics::make_matrix(
+            mana_position - Vector2::new(width, 0.0) * (1.0 - mana.mana / mana.max_mana) / 2.0,
+            Vector2::new(width * mana.mana / mana.max_mana, mana_height),
+            0.0);
+        simple_render.render(&(proj * matrix_back), &Vector4::new(0.2, 0.2, 0.2, 1.0), graphics::VertexRange::Full);
+        simple_render.render(&(proj * matrix_blue), &Vector4::new(0.2, 0.4, 1.0, 1.0), graphics::VertexRange::Full);
+    }
+}
+
 pub struct Render {
     _font_library: graphics::text::FontLibrary,
-    _texture_library: graphics::TextureLibrary,
+    texture_library: graphics::TextureLibrary,
     text: BTreeMap<i32, graphics::text::Font>,
     simple_render: graphics::simple::Renderer,
     texture_render: graphics::textured::Renderer,
@@ -80,6 +145,7 @@ pub struct Render {
     fireball_flames_grow_animation_textures: Vec<graphics::Texture>,
 
     map: Vec<MapLayer>,
+    map_id: Option<String>, // the map the layers were loaded from
     animation_data: HashMap<CharacterID, Animation>,
     animation_fps: f32,
     click_animation_timer: f32,
@@ -124,28 +190,13 @@ impl Render {
             |i| texture_library.make_texture(format!("fire_ball_cast_128/F{}.png", i).as_str(), &[graphics::TextureOptions::Bilinear])
         ).collect();
 
-        let map: Vec<MapLayer> = [("map/grass.png", "grass.png"), ("map/water.png", "water.png")].iter()
-            .map(|(map_file, texture)| {
-            let img_obj = image::io::Reader::open(map_file).unwrap().decode().unwrap();
-            let img = img_obj.as_rgba8().unwrap();
-            let img_data = img.as_raw();
-            let data: Vec<u8> = img_data.iter().skip(3).step_by(4).copied().collect();
-            MapLayer {
-                _width: img.width(),
-                _height: img.height(),
-                _data: data.iter().map(|pixel| *pixel as f32 / 255.0).collect(),
-                data_texture: texture_library.make_texture_from(img.width(), img.height(), &data, &[TextureOptions::Red, TextureOptions::Bilinear]),
-                texture: texture_library.make_texture(texture, &[TextureOptions::Repeating, TextureOptions::Bilinear])
-            }
-        }).collect();
-
         let animation_data: HashMap<CharacterID, Animation> = HashMap::new();
         let animation_fps = 12.0;
         let click_animation_timer = 0.0;
 
         Self {
             _font_library: font_library,
-            _texture_library: texture_library,
+            texture_library,
             text,
             simple_render,
             texture_render,
@@ -156,7 +207,8 @@ impl Render {
             fireball_animation_textures,
             fireball_ball_grow_animation_textures,
             fireball_flames_grow_animation_textures,
-            map,
+            map: vec![],
+            map_id: None,
             animation_data,
             animation_fps,
             click_animation_timer,
@@ -192,14 +244,46 @@ impl Render {
             c
         };
 
-        // display map
-        for MapLayer { _width: _, _height: _, _data: _, data_texture, texture } in self.map.iter() {
-            let full_scale = 16.0;
-            let tile_count = 8.0;
+        // display map, loading its layers when the server switches maps
+        if self.map_id.as_ref() != Some(&game.map.id) {
+            self.map = load_map_layers(&mut self.texture_library, &game.map);
+            self.map_id = Some(game.map.id.clone());
+        }
+        let full_scale = game.map.manifest.world_size;
+        for MapLayer { _width: _, _height: _, _data: _, data_texture, texture, tiles } in self.map.iter() {
+            let tile_count = *tiles;
             let matrix = graphics::make_matrix(Vector2::new(0.0, 0.0), Vector2::new(full_scale, full_scale), 0.0);
             self.map_render.render(&(proj_view * matrix), &Vector4::new(1.0, 1.0, 1.0, 1.0), texture, data_texture, tile_count, graphics::VertexRange::Full);
         }
 
+        // fog of war, darkest where our team has never been and dimmed where it can't see right now
+        // without a team everything is shown
+        let team = game.selected_team();
+        let visible: Option<HashSet<CharacterID>> = team.map(|team| vision::team_vision(&game.world, team));
+        if let Some(team) = team {
+            let viewers = vision::team_viewers(&game.world, team);
+            let world_size = game.map.manifest.world_size;
+            let cells = (world_size / FOG_CELL_SIZE).ceil() as i32;
+            for j in 0..cells {
+                for i in 0..cells {
+                    let cell = Vector2::new(i, j);
+                    let center = Vector2::new(i as f32 + 0.5, j as f32 + 0.5) * FOG_CELL_SIZE
+                        - Vector2::new(world_size, world_size) / 2.0;
+                    let darkness = if vision::in_sight(&game.world, &viewers, &center) {
+                        game.explored.insert(cell);
+                        continue;
+                    } else if game.explored.contains(&cell) {
+                        0.5
+                    } else {
+                        0.85
+                    };
+                    let matrix = graphics::make_matrix(center, Vector2::new(FOG_CELL_SIZE, FOG_CELL_SIZE), 0.0);
+                    self.simple_render.render(&(proj_view * matrix), &Vector4::new(0.0, 0.0, 0.0, darkness), graphics::VertexRange::Full);
+                }
+            }
+        }
+        let is_visible = |cid: &CharacterID| visible.as_ref().map(|visible| visible.contains(cid)).unwrap_or(true);
+
         for anim in self.standalone_animations.values_mut() {
             for anim in anim {
                 anim.timer += delta_time;
@@ -213,7 +297,7 @@ impl Render {
             StandaloneAnimation(Vector3<f32>, usize, CharacterFlip)
         }
         let mut renderables = vec![];
-        renderables.extend(game.world.characters.iter().map(|cid| Renderable::Character(*cid)));
+        renderables.extend(game.world.characters.iter().filter(|cid| is_visible(cid)).map(|cid| Renderable::Character(*cid)));
         let render_click = || -> Option<Vector2<f32>> {
             if game.destination != self.click_prev_dest {
                 self.click_prev_dest = game.destination;
@@ -247,7 +331,7 @@ impl Render {
         } else {
             None
         };
-        for (cid, auto_attack) in &game.world.auto_attack.components {
+        for (cid, auto_attack) in game.world.auto_attack.components.iter().filter(|(cid, _)| is_visible(cid)) {
             || -> Option<()> {
                 let frames = self.fireball_ball_grow_animation_textures.len();
                 let base = game.world.base.components.get(cid)?;
@@ -414,7 +498,10 @@ impl Render {
                                     let hovered = if let Some(hcid) = &game.hovered_character {
                                         *cid == *hcid
                                     } else { false };
-                                    let color = if hovered {
+                                    let alive = is_alive(&game.world, cid);
+                                    let color = if !alive {
+                                        Vector4::new(0.5, 0.5, 0.5, 0.5)
+                                    } else if hovered {
                                         Vector4::new(1.0, 0.8, 0.8, 1.0)
                                     } else {
                                         Vector4::new(1.0, 1.0, 1.0, 1.0)
@@ -448,28 +535,41 @@ impl Render {
                                         game_font.render(&(proj * sim.to_homogeneous()), name.as_str(), &Vector4::new(1.0, 1.0, 1.0, 1.0));
                                     }
 
-                                    if let Some(health) = game.world.health.components.get(cid) {
-                                        let max_health = health.max_health;
-                                        let health = health.health;
+                                    if alive {
                                         let width = match base.ctype {
                                             CharacterType::IceWiz => 150.0,
                                             CharacterType::CasterMinion => 100.0,
                                             _ => 100.0
                                         };
-                                        let height = 20.0;
                                         let position = game.camera.world_to_view_pos(Vector2::new(base.position.x, base.position.y) + Vector2::new(0.0, above));
-                                        let matrix_red = graphics::make_matrix(position, Vector2::new(width, height), 0.0);
-                                        let matrix_green = graphics::make_matrix(
-                                            position - Vector2::new(width, 0.0) * (1.0 - health / max_health) / 2.0,
-                                            Vector2::new(width * health / max_health, height),
-                                            0.0);
-                                        self.simple_render.render(&(proj * matrix_red), &Vector4::new(1.0, 0.0, 0.0, 1.0), graphics::VertexRange::Full);
-                                        self.simple_render.render(&(proj * matrix_green), &Vector4::new(0.0, 1.0, 0.0, 1.0), graphics::VertexRange::Full);
+                                        render_bars(&self.simple_render, &proj, &game.world, cid, selected_char, position, width);
                                     }
 
                                     Some(())
                                 })();
                             },
+                            // towers are drawn as a plain block until they get a sprite
+                            CharacterType::Tower => {
+                                let hovered = game.hovered_character.map(|hcid| hcid == *cid).unwrap_or(false);
+                                let alive = is_alive(&game.world, cid);
+                                let color = if !alive {
+                                    Vector4::new(0.3, 0.3, 0.3, 0.5)
+                                } else if hovered {
+                                    Vector4::new(0.8, 0.7, 0.7, 1.0)
+                                } else {
+                                    Vector4::new(0.6, 0.6, 0.65, 1.0)
+                                };
+                                let size = Vector2::new(0.5, 1.0);
+                                let matrix = graphics::make_matrix(
+                                    Vector2::new(base.position.x, base.position.y - size.y / 2.0),
+                                    size,
+                                    0.0);
+                                self.simple_render.render(&(proj_view * matrix), &color, graphics::VertexRange::Full);
+                                if alive {
+                                    let position = game.camera.world_to_view_pos(Vector2::new(base.position.x, base.position.y - size.y - 0.2));
+                                    render_bars(&self.simple_render, &proj, &game.world, cid, selected_char, position, 200.0);
+                                }
+                            },
                             CharacterType::Projectile => || -> Option<()> {
                                 let Animation { timer: animation_time,  } = match self.animation_data.get_mut(cid) {
                                     None => {
@@ -485,7 +585,10 @@ impl Render {
                                 let frame = regulate_extract_frame(animation_time, self.animation_fps, 0, textures.len());
                                 let position = base.position;
                                 let projectile = game.world.projectile.components.get(cid)?;
-                                let target_position = game.world.base.components.get(&projectile.target)?.position;
+                                let target_position = match &projectile.target {
+                                    ProjectileTarget::Character(target) => game.world.base.components.get(target)?.position,
+                                    ProjectileTarget::Direction(skillshot) => position + Vector3::new(skillshot.direction.x, skillshot.direction.y, 0.0),
+                                };
                                 // let flip_dir: f32 = match base.flip {
                                 //     CharacterFlip::Left => -1.0,
                                 //     CharacterFlip::Right => 1.0
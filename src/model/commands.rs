@@ -26,11 +26,22 @@ pub enum CommandID {
     ListChar,
     EnsureCharacter,
     IndicateClientPlayer,
+    FixWorldAck,
+    FixWorldNack,
+    ReadyRequest,
 
     // commands to run on client
     SendAddress,
     PlayerDataPayload,
     FixWorld,
+    WorldStateHash,
+    WorldSnapshot,
+    Handshake,
+    VisionChange,
+    LobbyStatus,
+    MatchCountdown,
+    MatchStarted,
+    MatchEnded,
 
     // commands to run on both
     EchoMessage,
@@ -39,6 +50,10 @@ pub enum CommandID {
     // world commands (also on both, but focus run on world)
     AutoAttackRequest,
     FlashRequest,
+    AoeRequest,
+    BoltRequest,
+    BuyRequest,
+    SellRequest,
     ClearWorld,
     RunWorldCommand
 }
@@ -0,0 +1,63 @@
+use crate::{client::{commands::ClientCommand, game::Game}, networking::Protocol, model::player::model::{PlayerID, PlayerDataView, TeamID}};
+use super::{commands::{LobbyStatus, MatchCountdown, MatchStarted, MatchEnded}, model::VictoryReason};
+
+fn player_name(game: &Game, player: &PlayerID) -> String {
+    game.players.get_player(player)
+        .map(|player| player.name.clone())
+        .unwrap_or_else(|| format!("{:?}", player))
+}
+
+fn team_name(game: &Game, team: &TeamID) -> String {
+    game.players.teams.get(team)
+        .map(|team| team.name.clone())
+        .unwrap_or_else(|| format!("{:?}", team))
+}
+
+impl<'a> ClientCommand<'a> for LobbyStatus {
+    fn run(self, (_, game): (Protocol, &mut Game)) {
+        let waiting: Vec<String> = self.players.iter()
+            .filter(|player| !self.ready.contains(player))
+            .map(|player| player_name(game, player))
+            .collect();
+        game.chatbox.println(format!("Lobby: {}/{} players ready", self.ready.len(), self.players.len()).as_str());
+        if !waiting.is_empty() {
+            game.chatbox.println(format!("Waiting for {}", waiting.join(", ")).as_str());
+        }
+    }
+}
+
+impl<'a> ClientCommand<'a> for MatchCountdown {
+    fn run(self, (_, game): (Protocol, &mut Game)) {
+        match self.0 {
+            Some(seconds) => game.chatbox.println(format!("Match starts in {}...", seconds).as_str()),
+            None => game.chatbox.println("Countdown cancelled"),
+        }
+    }
+}
+
+impl<'a> ClientCommand<'a> for MatchStarted {
+    fn run(self, (_, game): (Protocol, &mut Game)) {
+        game.chatbox.println("Match started!");
+    }
+}
+
+impl<'a> ClientCommand<'a> for MatchEnded {
+    fn run(self, (_, game): (Protocol, &mut Game)) {
+        let summary = self.0;
+        let reason = match summary.reason {
+            VictoryReason::StructuresDestroyed => "destroying every enemy structure",
+            VictoryReason::KillLimit => "reaching the kill limit",
+        };
+        match &summary.winner {
+            Some(team) => game.chatbox.println(format!("{} won by {}", team_name(game, team), reason).as_str()),
+            None => game.chatbox.println("The match ended in a draw"),
+        }
+        game.chatbox.println(format!("Match length: {}:{:02}", summary.duration as u32 / 60, summary.duration as u32 % 60).as_str());
+        for (team, kills) in &summary.team_kills {
+            game.chatbox.println(format!("{}: {} kills", team_name(game, team), kills).as_str());
+        }
+        for (player, stats) in &summary.players {
+            game.chatbox.println(format!("{}: {}/{}", player_name(game, player), stats.kills, stats.deaths).as_str());
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use serde::{Serialize, Deserialize};
+use crate::model::{commands::{GetCommandID, CommandID}, player::model::PlayerID};
+use super::model::MatchSummary;
+
+// sent by a player in the lobby when they are ready to start, or no longer ready
+#[derive(Serialize, Deserialize)]
+pub struct ReadyRequest(pub bool);
+
+// who is in the lobby and who of them is ready
+#[derive(Serialize, Deserialize)]
+pub struct LobbyStatus {
+    pub players: Vec<PlayerID>,
+    pub ready: Vec<PlayerID>,
+}
+
+// seconds until the match starts, or None when the countdown was cancelled
+#[derive(Serialize, Deserialize)]
+pub struct MatchCountdown(pub Option<u32>);
+
+#[derive(Serialize, Deserialize)]
+pub struct MatchStarted;
+
+#[derive(Serialize, Deserialize)]
+pub struct MatchEnded(pub MatchSummary);
+
+impl GetCommandID for ReadyRequest {
+    fn command_id(&self) -> crate::model::commands::CommandID {
+        CommandID::ReadyRequest
+    }
+}
+
+impl GetCommandID for LobbyStatus {
+    fn command_id(&self) -> crate::model::commands::CommandID {
+        CommandID::LobbyStatus
+    }
+}
+
+impl GetCommandID for MatchCountdown {
+    fn command_id(&self) -> crate::model::commands::CommandID {
+        CommandID::MatchCountdown
+    }
+}
+
+impl GetCommandID for MatchStarted {
+    fn command_id(&self) -> crate::model::commands::CommandID {
+        CommandID::MatchStarted
+    }
+}
+
+impl GetCommandID for MatchEnded {
+    fn command_id(&self) -> crate::model::commands::CommandID {
+        CommandID::MatchEnded
+    }
+}
@@ -0,0 +1,9 @@
+pub mod model;
+
+#[cfg(feature = "client")]
+pub mod client;
+
+#[cfg(feature = "server")]
+pub mod server;
+
+pub mod commands;
@@ -0,0 +1,223 @@
+use std::collections::{HashMap, HashSet};
+use serde::{Serialize, Deserialize};
+use crate::model::{player::model::{PlayerID, TeamID}, world::{World, WorldEvent, character::{CharacterID, CharacterType}, system::{death::is_alive, team::get_team}}};
+
+// seconds between everyone being ready and the match starting
+pub const COUNTDOWN_TIME: f32 = 5.0;
+
+// seconds the summary is shown before going back to the lobby
+pub const POST_GAME_TIME: f32 = 10.0;
+
+// champion kills a team needs to win
+pub const KILL_LIMIT: u32 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchPhase {
+    Lobby,
+    Countdown(f32), // seconds left
+    InProgress,
+    PostGame(f32), // seconds left
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VictoryReason {
+    StructuresDestroyed, // every structure of the other team is destroyed
+    KillLimit,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct PlayerStats {
+    pub kills: u32,
+    pub deaths: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MatchSummary {
+    pub winner: Option<TeamID>,
+    pub reason: VictoryReason,
+    pub duration: f32, // seconds
+    pub team_kills: Vec<(TeamID, u32)>,
+    pub players: Vec<(PlayerID, PlayerStats)>,
+}
+
+pub enum MatchUpdate {
+    Countdown(u32), // whole seconds left, sent once per second
+    CountdownCancelled,
+    Start,
+    End(MatchSummary),
+    Reset,
+}
+
+// a player in the match, with their team and the character they control
+pub type MatchPlayer = (PlayerID, Option<TeamID>, Option<CharacterID>);
+
+pub struct MatchManager {
+    pub phase: MatchPhase,
+    ready: HashSet<PlayerID>,
+    elapsed: f32,
+    team_kills: HashMap<TeamID, u32>,
+    stats: HashMap<PlayerID, PlayerStats>,
+    defended: HashSet<TeamID>, // teams that have had a structure this match
+}
+
+impl MatchManager {
+    pub fn new() -> Self {
+        Self {
+            phase: MatchPhase::Lobby,
+            ready: HashSet::new(),
+            elapsed: 0.0,
+            team_kills: HashMap::new(),
+            stats: HashMap::new(),
+            defended: HashSet::new(),
+        }
+    }
+
+    pub fn in_progress(&self) -> bool {
+        self.phase == MatchPhase::InProgress
+    }
+
+    pub fn ready_players(&self) -> Vec<PlayerID> {
+        self.ready.iter().copied().collect()
+    }
+
+    pub fn set_ready(&mut self, player: PlayerID, ready: bool) -> Result<(), String> {
+        match self.phase {
+            MatchPhase::Lobby | MatchPhase::Countdown(_) => {
+                if ready {
+                    self.ready.insert(player);
+                } else {
+                    self.ready.remove(&player);
+                }
+                Ok(())
+            },
+            _ => Err("The match has already started".to_string()),
+        }
+    }
+
+    // runs once per tick, after the world has updated
+    pub fn update(&mut self, delta_time: f32, world: &World, players: &[MatchPlayer]) -> Vec<MatchUpdate> {
+        self.ready.retain(|ready| players.iter().any(|(player, _, _)| player == ready));
+        let all_ready = !players.is_empty() && players.iter().all(|(player, _, _)| self.ready.contains(player));
+        match self.phase {
+            MatchPhase::Lobby if all_ready => {
+                self.phase = MatchPhase::Countdown(COUNTDOWN_TIME);
+                vec![MatchUpdate::Countdown(COUNTDOWN_TIME.ceil() as u32)]
+            },
+            MatchPhase::Lobby => vec![],
+            MatchPhase::Countdown(_) if !all_ready => {
+                self.phase = MatchPhase::Lobby;
+                vec![MatchUpdate::CountdownCancelled]
+            },
+            MatchPhase::Countdown(left) => {
+                let next = left - delta_time;
+                if next <= 0.0 {
+                    self.phase = MatchPhase::InProgress;
+                    self.elapsed = 0.0;
+                    self.team_kills.clear();
+                    self.stats.clear();
+                    self.defended.clear();
+                    vec![MatchUpdate::Start]
+                } else {
+                    self.phase = MatchPhase::Countdown(next);
+                    if next.ceil() < left.ceil() {
+                        vec![MatchUpdate::Countdown(next.ceil() as u32)]
+                    } else {
+                        vec![]
+                    }
+                }
+            },
+            // everyone left, so there is nobody to win
+            MatchPhase::InProgress if players.is_empty() => {
+                self.phase = MatchPhase::Lobby;
+                self.ready.clear();
+                vec![MatchUpdate::Reset]
+            },
+            MatchPhase::InProgress => {
+                self.elapsed += delta_time;
+                self.count_kills(world, players);
+                match self.victory(world, players) {
+                    Some(summary) => {
+                        self.phase = MatchPhase::PostGame(POST_GAME_TIME);
+                        vec![MatchUpdate::End(summary)]
+                    },
+                    None => vec![],
+                }
+            },
+            MatchPhase::PostGame(left) => {
+                if left - delta_time <= 0.0 {
+                    self.phase = MatchPhase::Lobby;
+                    self.ready.clear();
+                    vec![MatchUpdate::Reset]
+                } else {
+                    self.phase = MatchPhase::PostGame(left - delta_time);
+                    vec![]
+                }
+            },
+        }
+    }
+
+    fn count_kills(&mut self, world: &World, players: &[MatchPlayer]) {
+        let owner = |cid: &CharacterID| players.iter()
+            .find(|(_, _, character)| *character == Some(*cid))
+            .map(|(player, _, _)| *player);
+        for event in &world.events {
+            if let WorldEvent::Kill { target, killer } = event {
+                // only champions that players control count
+                let target_owner = match owner(target) {
+                    Some(player) => player,
+                    None => continue,
+                };
+                self.stats.entry(target_owner).or_default().deaths += 1;
+                if let Some(killer) = killer {
+                    if let Some(killer_owner) = owner(killer) {
+                        self.stats.entry(killer_owner).or_default().kills += 1;
+                    }
+                    if let Some(team) = get_team(world, killer) {
+                        *self.team_kills.entry(team).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    fn victory(&mut self, world: &World, players: &[MatchPlayer]) -> Option<MatchSummary> {
+        // a team is defeated once it has had structures and all of them are destroyed
+        let standing: HashSet<TeamID> = world.base.components.iter()
+            .filter(|(cid, base)| base.ctype == CharacterType::Tower && is_alive(world, cid))
+            .filter_map(|(cid, _)| get_team(world, cid))
+            .collect();
+        self.defended.extend(standing.iter().copied());
+        let teams: HashSet<TeamID> = players.iter().filter_map(|(_, team, _)| *team)
+            .chain(self.defended.iter().copied())
+            .collect();
+        let other_team = |loser: TeamID| {
+            let others: Vec<TeamID> = teams.iter().copied().filter(|team| *team != loser).collect();
+            match others.as_slice() {
+                [winner] => Some(*winner),
+                _ => None,
+            }
+        };
+        let result = if let Some(loser) = self.defended.iter().copied().find(|team| !standing.contains(team)) {
+            Some((other_team(loser), VictoryReason::StructuresDestroyed))
+        } else {
+            self.team_kills.iter()
+                .find(|(_, kills)| **kills >= KILL_LIMIT)
+                .map(|(team, _)| (Some(*team), VictoryReason::KillLimit))
+        };
+        result.map(|(winner, reason)| MatchSummary {
+            winner,
+            reason,
+            duration: self.elapsed,
+            team_kills: self.team_kills.iter().map(|(team, kills)| (*team, *kills)).collect(),
+            players: players.iter()
+                .map(|(player, _, _)| (*player, self.stats.get(player).copied().unwrap_or_default()))
+                .collect(),
+        })
+    }
+}
+
+impl Default for MatchManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,14 @@
+use std::net::SocketAddr;
+use crate::{server::{commands::{ProtocolSpec, SendCommands}, main::Server}, networking::Protocol, model::{PrintError, player::{server::PlayerCommand, model::PlayerID, commands::ChatMessage}}};
+use super::commands::ReadyRequest;
+
+impl<'a> PlayerCommand<'a> for ReadyRequest {
+    const PROTOCOL: ProtocolSpec = ProtocolSpec::One(Protocol::TCP);
+
+    fn run(self, addr: &SocketAddr, player_id: &PlayerID, server: &mut Server) {
+        match server.matches.set_ready(*player_id, self.0) {
+            Ok(()) => server.broadcast_lobby_status(),
+            Err(err) => server.connection.send(Protocol::TCP, addr, &ChatMessage(err)).print(),
+        }
+    }
+}
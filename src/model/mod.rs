@@ -4,6 +4,7 @@ use serde::{Serialize, Deserialize};
 use strum_macros::EnumString;
 
 pub mod player;
+pub mod lobby;
 pub mod world;
 pub mod commands;
 pub mod util;
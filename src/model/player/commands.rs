@@ -14,7 +14,10 @@ pub struct GetPlayerData;
 #[derive(Serialize, Deserialize, Debug)]
 pub struct PlayerLogIn {
     pub existing: bool,
-    pub name: Option<String>
+    pub name: Option<String>,
+    // the server refuses clients that would simulate a different world
+    pub definitions_hash: u64,
+    pub map_hash: u64,
 }
 
 #[derive(Serialize, Deserialize)]
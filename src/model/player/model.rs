@@ -2,33 +2,42 @@ use std::{collections::{HashMap, HashSet}, fmt::Display, net::SocketAddr};
 use serde::{Serialize, Deserialize};
 use crate::model::{Subscription, world::character::CharacterID};
 
-// #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy)]
-// pub struct TeamID(i32);
-// 
-// #[derive(Serialize, Deserialize, Debug)]
-// pub struct TeamIDGenerator(i32);
-// 
-// impl TeamIDGenerator {
-//     pub fn new() -> Self {
-//         TeamIDGenerator(0)
-//     }
-//     pub fn generate(&mut self) -> TeamID {
-//         self.0 += 1;
-//         TeamID(self.0 - 1)
-//     }
-// }
-// 
-// impl Default for TeamIDGenerator {
-//     fn default() -> Self {
-//         Self::new()
-//     }
-// }
-// 
-// #[derive(Serialize, Deserialize, Debug, Clone)]
-// pub struct Team {
-//     id: TeamID,
-//     name: String
-// }
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub struct TeamID(i32);
+
+impl TeamID {
+    // teams are numbered in the order they are made, which is also the side of the map they start on
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TeamIDGenerator(i32);
+
+impl TeamIDGenerator {
+    pub fn new() -> Self {
+        TeamIDGenerator(0)
+    }
+    pub fn generate(&mut self) -> TeamID {
+        self.0 += 1;
+        TeamID(self.0 - 1)
+    }
+}
+
+impl Default for TeamIDGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Team {
+    pub id: TeamID,
+    pub name: String
+}
+
+const TEAM_NAMES: [&str; 2] = ["Blue", "Red"];
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub struct PlayerID(i32);
@@ -62,7 +71,8 @@ impl Default for PlayerIDGenerator {
 pub struct Player {
    pub id: PlayerID,
    pub name: String,
-   pub selected_char: Option<CharacterID>
+   pub selected_char: Option<CharacterID>,
+   pub team: Option<TeamID>,
 }
 
 
@@ -82,6 +92,7 @@ pub struct PlayerManager {
     players: HashMap<PlayerID, Player>,
     id_gen: PlayerIDGenerator,
     player_metadata: HashMap<PlayerID, PlayerMetadata>,
+    teams: HashMap<TeamID, Team>,
     pub updates: Vec<PlayerManagerUpdate>
 }
 
@@ -93,14 +104,32 @@ pub enum PlayerManagerUpdate {
 
 impl PlayerManager {
     pub fn new() -> PlayerManager {
+        let mut team_id_gen = TeamIDGenerator::new();
         PlayerManager {
             players: HashMap::new(),
             player_metadata: HashMap::new(),
             id_gen: PlayerIDGenerator::new(),
+            teams: TEAM_NAMES.iter().map(|name| {
+                let id = team_id_gen.generate();
+                (id, Team { id, name: name.to_string() })
+            }).collect(),
             updates: vec![]
         }
     }
 
+    pub fn team_ids(&self) -> Vec<TeamID> {
+        let mut ids: Vec<TeamID> = self.teams.keys().copied().collect();
+        ids.sort();
+        ids
+    }
+
+    // the team with the fewest players, so that new players even the teams out
+    fn smallest_team(&self) -> Option<TeamID> {
+        self.teams.keys()
+            .min_by_key(|id| (self.players.values().filter(|player| player.team == Some(**id)).count(), **id))
+            .copied()
+    }
+
     pub fn create_player(&mut self, con: Option<SocketAddr>, name: Option<String>) -> &mut Player {
         let id = self.id_gen.generate();
         let name = {
@@ -130,10 +159,12 @@ impl PlayerManager {
             subscriptions: HashSet::new()
         });
 
+        let team = self.smallest_team();
         self.players.insert(id, Player {
             id,
             name,
-            selected_char: None
+            selected_char: None,
+            team,
         });
         self.updates.push(PlayerManagerUpdate::PlayerInfoUpdate(id));
         self.map_existing_player(con.as_ref(), Some(&id));
@@ -197,7 +228,8 @@ impl PlayerManager {
 
     pub fn get_view(&self) -> PlayerData {
         PlayerData {
-            players: self.players.clone()
+            players: self.players.clone(),
+            teams: self.teams.clone(),
         }
     }
 
@@ -279,7 +311,8 @@ impl PlayerDataView for PlayerManager {
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PlayerData {
-   pub players: HashMap<PlayerID, Player>
+   pub players: HashMap<PlayerID, Player>,
+   pub teams: HashMap<TeamID, Team>,
 }
 
 impl PlayerDataView for PlayerData {
@@ -25,7 +25,11 @@ impl<'a> ProtocolServerCommand<'a> for PlayerLogIn {
     const PROTOCOL: ProtocolSpec = ProtocolSpec::One(Protocol::TCP);
     fn run(self, _: Protocol, addr: &SocketAddr, server: &mut Server) {
         match {
-            if self.existing {
+            if self.definitions_hash != server.definitions_hash {
+                Err("Cannot sign in: character definitions don't match the server's".to_string())
+            } else if self.map_hash != server.map.hash {
+                Err(format!("Cannot sign in: map {} doesn't match the server's", server.map.id))
+            } else if self.existing {
                 if let Some(name) = &self.name {
                     match if let Some(player) = server.player_manager.get_player_with_name(name) {
                         Ok(player.id)
@@ -134,6 +138,9 @@ impl<'a> PlayerCommand<'a> for PlayerSubs {
                     format!("{:?}", add)
                 }).collect::<Vec<String>>().join(" ");
                 server.connection.send(Protocol::TCP, addr, &ChatMessage(msg)).print();
+                if added.contains(&Subscription::World) {
+                    server.send_world_snapshot(addr);
+                }
             },
             PlayerSubCommand::DelSubs(to_del) => {
                 let mut deleted = vec![];
@@ -152,12 +159,17 @@ impl<'a> PlayerCommand<'a> for PlayerSubs {
             },
             PlayerSubCommand::SetSubs(new_subs) => {
                 if let Some(subs) = server.player_manager.get_player_subscriptions_mut(player_id) {
+                    let had_world = subs.contains(&Subscription::World);
                     subs.drain();
                     subs.extend(new_subs);
+                    let added_world = !had_world && subs.contains(&Subscription::World);
                     let msg = String::from("Replaced subscriptions: ") +
                         &subs.iter().map(|s| format!("{:?}", s))
                         .collect::<Vec<String>>().join(" ");
                     server.connection.send(Protocol::TCP, addr, &ChatMessage(msg)).print();
+                    if added_world {
+                        server.send_world_snapshot(addr);
+                    }
                 } else {
                     server.connection.send(Protocol::TCP, addr, &ChatMessage("Failed to replace subscriptions, player metadata is missing".to_string())).print();
                 }
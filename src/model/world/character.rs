@@ -1,5 +1,24 @@
+use nalgebra::{Vector2, Vector3};
 use serde::{Serialize, Deserialize};
 
+use crate::model::player::model::TeamID;
+use super::{World, WorldError, WorldErrorI, Update, WorldUpdate, component::{ComponentID, ComponentUpdate, ComponentUpdateData}, system::{
+    auto_attack::{AutoAttack, AutoAttackUpdate},
+    base::CharacterBaseUpdate,
+    health::CharacterHealthUpdate,
+    status::{StatusUpdate, idle_status},
+    modifiers::ModifiersUpdate,
+    team::{CharacterTeamUpdate, CharacterTeam},
+    collision::CollisionUpdate,
+    death::DeathUpdate,
+    mana::ManaUpdate,
+    experience::ExperienceUpdate,
+    gold::GoldUpdate,
+    inventory::InventoryUpdate,
+    aoe::AoeUpdate,
+    bolt::BoltUpdate,
+}};
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
 pub struct CharacterID(u64);
 
@@ -13,7 +32,7 @@ impl CharacterID {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct CharacterIDGenerator(u64);
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -71,5 +90,62 @@ pub enum CharacterType {
     IceWiz,
     CasterMinion,
     Projectile,
+    Tower,
 }
 
+impl CharacterType {
+    // how the character is referred to in chat, ex: kill announcements
+    pub fn describe(&self) -> &'static str {
+        match self {
+            CharacterType::Unknown => "something",
+            CharacterType::IceWiz => "an ice wizard",
+            CharacterType::CasterMinion => "a caster minion",
+            CharacterType::Projectile => "a projectile",
+            CharacterType::Tower => "a tower",
+        }
+    }
+}
+
+// the updates that create a character: the components every character type has, the ones its
+// definition asks for, and the ones the type adds itself, ex: movement
+pub fn create_common_components(
+    world: &World,
+    cid: &CharacterID,
+    typ: CharacterType,
+    position: Vector2<f32>,
+    team: Option<TeamID>,
+    components: impl IntoIterator<Item = ComponentUpdateData>
+) -> Result<Vec<Update>, WorldError> {
+    let cid = *cid;
+    // start these two at base stats
+    let mut base = *world.info.base.get(&typ)
+        .ok_or_else(|| WorldErrorI::MissingCharacterInfoComponent(typ, ComponentID::Base).err())?;
+    base.position = Vector3::new(position.x, position.y, 0.0);
+    let health = world.info.health.get(&typ)
+        .ok_or_else(|| WorldErrorI::MissingCharacterInfoComponent(typ, ComponentID::Health).err())?
+        .health;
+    Ok([
+        ComponentUpdateData::Base(CharacterBaseUpdate::New(base)),
+        ComponentUpdateData::Health(CharacterHealthUpdate::New(health)),
+        ComponentUpdateData::AutoAttack(AutoAttackUpdate(AutoAttack::new())),
+        ComponentUpdateData::Status(StatusUpdate::New(idle_status())),
+        ComponentUpdateData::Modifiers(ModifiersUpdate::New),
+        ComponentUpdateData::Team(CharacterTeamUpdate(CharacterTeam { team })),
+    ].into_iter()
+    .chain(components)
+    .chain(world.info.collision.get(&typ).cloned().map(|collision| ComponentUpdateData::Collision(CollisionUpdate(collision))))
+    .chain(world.info.death.get(&typ).map(|_| ComponentUpdateData::Death(DeathUpdate::New)))
+    .chain(world.info.mana.get(&typ).map(|mana| ComponentUpdateData::Mana(ManaUpdate::New(mana.max_mana))))
+    .chain(world.info.experience.get(&typ).map(|_| ComponentUpdateData::Experience(ExperienceUpdate::New)))
+    // characters with gold can shop
+    .chain(world.info.gold.get(&typ).map(|gold| ComponentUpdateData::Gold(GoldUpdate::New(gold.starting))))
+    .chain(world.info.gold.get(&typ).map(|_| ComponentUpdateData::Inventory(InventoryUpdate::New)))
+    .chain(world.info.aoe.get(&typ).map(|_| ComponentUpdateData::Aoe(AoeUpdate::New)))
+    .chain(world.info.bolt.get(&typ).map(|_| ComponentUpdateData::Bolt(BoltUpdate::New)))
+    .map(|cud| Update::Comp(ComponentUpdate {
+        cid,
+        data: cud,
+    }))
+    .chain(Some(Update::World(WorldUpdate::NewCharacterID(cid))))
+    .collect())
+}
@@ -1,8 +1,8 @@
 use std::collections::HashMap;
 
-use crate::{networking::Protocol, client::{game::{Game, TickCommand}, commands::ClientCommand}};
+use crate::{networking::Protocol, client::{game::{Game, TickCommand}, commands::{ClientCommand, SendCommands}}, model::PrintError};
 
-use super::{commands::{ClearWorld, RunWorldCommand, FixWorld}, World, system::collision::CollisionInfo};
+use super::{commands::{ClearWorld, RunWorldCommand, FixWorld, FixWorldAck, FixWorldNack, WorldStateHash, WorldSnapshot, Handshake, VisionChange}, World, WorldTick, replication, map::MapPackage};
 
 // impl<'a> ClientCommand<'a> for UpdateCharacter {
 //     fn run(self, (_, game): (Protocol, &mut Game)) {
@@ -12,16 +12,49 @@ use super::{commands::{ClearWorld, RunWorldCommand, FixWorld}, World, system::co
 
 impl<'a> ClientCommand<'a> for ClearWorld {
     fn run(self, (_, game): (Protocol, &mut Game)) {
-        game.world = World::new(CollisionInfo::test_collision());
+        game.world = World::from(&game.world_template);
         for cid in game.world.characters.clone() {
             game.world.erase_character(&cid).ok();
         }
+        game.explored.clear();
     }
 }
 
+// how many acknowledged states to keep per character, in case the server's acks are delayed
+const MAX_FIX_BASELINES: usize = 16;
+
 impl<'a> ClientCommand<'a> for FixWorld {
     fn run(self, (_, game): (Protocol, &mut Game)) {
-        let command = TickCommand::FixWorld(self);
+        let baselines = game.fix_baselines.entry(self.id).or_default();
+        let baseline = match self.baseline {
+            None => None,
+            Some(tick) => match baselines.get(&tick) {
+                Some(baseline) => Some(baseline),
+                None => {
+                    // ask for the whole character instead, since every later fix would build on this one
+                    println!("FixWorld for {:?} refers to unknown baseline tick {}", self.id, tick);
+                    game.connection.send(Protocol::UDP, &FixWorldNack { id: self.id }).print();
+                    return
+                },
+            }
+        };
+        let update = match replication::resolve_delta(&self, baseline) {
+            Ok(update) => update,
+            Err(err) => {
+                println!("Error unpacking FixWorld for {:?}: {}", self.id, err);
+                return
+            },
+        };
+        // the server never sends a baseline older than one it already used
+        if let Some(tick) = self.baseline {
+            baselines.retain(|baseline_tick, _| *baseline_tick >= tick);
+        }
+        baselines.insert(self.tick, update.components.clone());
+        while baselines.len() > MAX_FIX_BASELINES {
+            baselines.pop_first();
+        }
+        game.connection.send(Protocol::UDP, &FixWorldAck { id: self.id, tick: self.tick }).print();
+        let command = TickCommand::FixWorld(self.tick, self.ordering, update);
         add_tick_command(command, game);
     }
 }
@@ -34,15 +67,72 @@ impl<'a> ClientCommand<'a> for RunWorldCommand {
     }
 }
 
+impl<'a> ClientCommand<'a> for VisionChange {
+    fn run(self, (_, game): (Protocol, &mut Game)) {
+        let command = TickCommand::VisionChange(self.tick, self.ordering, self.updated, self.left);
+        add_tick_command(command, game);
+    }
+}
+
+impl<'a> ClientCommand<'a> for Handshake {
+    fn run(self, (_, game): (Protocol, &mut Game)) {
+        if self.definitions_hash != game.definitions.hash {
+            game.chatbox.println(format!(
+                "Refused by server: character definitions don't match (server {:x}, client {:x})",
+                self.definitions_hash, game.definitions.hash).as_str());
+            game.connection.disconnect(None);
+            return;
+        }
+        if self.map != game.map.id {
+            match MapPackage::load(self.map.as_str()) {
+                Ok(map) => {
+                    game.chatbox.println(format!("Loaded map {}", map.manifest.name).as_str());
+                    game.set_map(map);
+                },
+                Err(err) => {
+                    game.chatbox.println(format!("Refused by server: failed to load map {}: {:?}", self.map, err).as_str());
+                    game.connection.disconnect(None);
+                    return;
+                },
+            }
+        }
+        if self.map_hash != game.map.hash {
+            game.chatbox.println(format!(
+                "Refused by server: map {} doesn't match (server {:x}, client {:x})",
+                self.map, self.map_hash, game.map.hash).as_str());
+            game.connection.disconnect(None);
+        }
+    }
+}
+
+impl<'a> ClientCommand<'a> for WorldStateHash {
+    fn run(self, (_, game): (Protocol, &mut Game)) {
+        game.server_hashes.insert(self.tick, self.hash);
+    }
+}
+
+impl<'a> ClientCommand<'a> for WorldSnapshot {
+    fn run(self, (_, game): (Protocol, &mut Game)) {
+        count_server_tick(self.tick, game);
+        game.world_snapshot = Some(self);
+    }
+}
+
+// record which server tick arrived at the current base tick, used to choose the display tick
+fn count_server_tick(tick: WorldTick, game: &mut Game) {
+    let offset = 0;
+    let server_tick_slot = game.tick_count.entry(game.tick_base + offset).or_insert_with(HashMap::new);
+    *server_tick_slot.entry(tick - game.tick_base + offset).or_insert(0) += 1;
+}
+
 pub fn add_tick_command(command: TickCommand, game: &mut Game) {
     // todo: discard old ticks
-    let offset = 0;
     let (tick, ordering) = match &command {
         TickCommand::WorldCommand(tick, ordering, _wc) => (tick, ordering),
-        TickCommand::FixWorld(FixWorld { update: _, ordering, tick }) => (tick, ordering),
+        TickCommand::FixWorld(tick, ordering, _update) => (tick, ordering),
+        TickCommand::VisionChange(tick, ordering, _updated, _left) => (tick, ordering),
     };
-    let server_tick_slot = game.tick_count.entry(game.tick_base + offset).or_insert_with(HashMap::new);
-    *server_tick_slot.entry(tick - game.tick_base + offset).or_insert(0) += 1;
+    count_server_tick(*tick, game);
 
     // add command to its correct tick in the sorted position according to "ordering"
     match game.tick_commands.get_mut(tick) {
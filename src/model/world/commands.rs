@@ -1,9 +1,10 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use nalgebra::Vector2;
 use serde::{Serialize, Deserialize};
 
-use crate::model::{commands::GetCommandID, WorldTick};
+use crate::model::{commands::GetCommandID, WorldTick, player::model::TeamID};
 
-use super::{World, character::{CharacterID, CharacterType}, component::ComponentID, system::{movement::MoveCharacter, auto_attack::AutoAttackCommand, flash::FlashCommand}, WorldError};
+use super::{World, character::{CharacterID, CharacterType, CharacterIDGenerator}, component::ComponentID, system::{movement::MoveCharacter, auto_attack::AutoAttackCommand, flash::FlashCommand, aoe::AoeCommand, bolt::BoltCommand, inventory::ShopCommand}, WorldError};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum WorldCommand {
@@ -14,7 +15,7 @@ pub enum WorldCommand {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum GlobalCommand {
     Clear,
-    CreateCharacter(CharacterID, CharacterType),
+    CreateCharacter(CharacterID, CharacterType, Option<TeamID>, Vector2<f32>), // id, type, team, position
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -22,6 +23,9 @@ pub enum CharacterCommand {
     Movement(MoveCharacter),
     AutoAttack(AutoAttackCommand),
     Flash(FlashCommand),
+    Aoe(AoeCommand),
+    Bolt(BoltCommand),
+    Shop(ShopCommand),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -42,9 +46,14 @@ impl UpdateCharacter {
     }
 }
 
+// state of one character relative to the last state of it that the client acknowledged
+// only changed components are included, packed against the baseline (see replication.rs)
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FixWorld {
-    pub update: UpdateCharacter,
+    pub id: CharacterID,
+    pub baseline: Option<WorldTick>, // None means every component is included
+    pub changed: HashMap<ComponentID, Vec<u8>>,
+    pub removed: Vec<ComponentID>,
     pub ordering: u32,
     pub tick: WorldTick,
 }
@@ -55,6 +64,77 @@ impl GetCommandID for FixWorld {
     }
 }
 
+// sent by the client once it has applied a FixWorld, making it the baseline for the next one
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FixWorldAck {
+    pub id: CharacterID,
+    pub tick: WorldTick,
+}
+
+impl GetCommandID for FixWorldAck {
+    fn command_id(&self) -> crate::model::commands::CommandID {
+        crate::model::commands::CommandID::FixWorldAck
+    }
+}
+
+// sent by the client when a FixWorld refers to a baseline it no longer has, ex: after loading a
+// snapshot. the next FixWorld for the character then contains every component
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FixWorldNack {
+    pub id: CharacterID,
+}
+
+impl GetCommandID for FixWorldNack {
+    fn command_id(&self) -> crate::model::commands::CommandID {
+        crate::model::commands::CommandID::FixWorldNack
+    }
+}
+
+// hash of the world the client should have at the start of a tick, before that tick's commands
+// are run. the server keeps a copy of each client's world to find it, see server/vision.rs
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WorldStateHash {
+    pub tick: WorldTick,
+    pub hash: u64,
+}
+
+impl GetCommandID for WorldStateHash {
+    fn command_id(&self) -> crate::model::commands::CommandID {
+        crate::model::commands::CommandID::WorldStateHash
+    }
+}
+
+// characters that entered or left what the client is sent at the start of this tick, which is what
+// its team can see inside its area of interest
+// updated characters come with their full state. they either just entered, or something the client
+// isn't sent changed them, ex: a hidden enemy's attack, so the client simulated them differently
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VisionChange {
+    pub tick: WorldTick,
+    pub ordering: u32,
+    pub updated: Vec<UpdateCharacter>,
+    pub left: Vec<CharacterID>,
+}
+
+// updated characters replace whatever the world had of them, including components it shouldn't have
+pub fn apply_vision_change(world: &mut World, updated: &[UpdateCharacter], left: &[CharacterID]) {
+    for update in updated {
+        world.erase_character(&update.id).ok();
+        if let Err(err) = update.update_character(world) {
+            world.errors.push(err);
+        }
+    }
+    for cid in left {
+        world.erase_character(cid).ok();
+    }
+}
+
+impl GetCommandID for VisionChange {
+    fn command_id(&self) -> crate::model::commands::CommandID {
+        crate::model::commands::CommandID::VisionChange
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Copy)]
 pub enum Priority {
     Walk,
@@ -136,3 +216,66 @@ impl GetCommandID for RunWorldCommand {
         crate::model::commands::CommandID::RunWorldCommand
     }
 }
+
+// the entire world state, sent over TCP when a client subscribes to the world so that it can start
+// simulating from the server's exact tick instead of waiting for every FixWorld
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WorldSnapshot {
+    pub tick: WorldTick,
+    pub characters: Vec<UpdateCharacter>,
+    pub id_gen: CharacterIDGenerator,
+}
+
+impl WorldSnapshot {
+    // only the given characters are included, ex: the ones the client's team can see
+    pub fn new(world: &World, id_gen: &CharacterIDGenerator, visible: &HashSet<CharacterID>) -> Self {
+        let mut characters: Vec<CharacterID> = world.characters.intersection(visible).copied().collect();
+        characters.sort();
+        Self {
+            tick: world.tick,
+            characters: characters.into_iter().map(|id| UpdateCharacter {
+                id,
+                components: world.get_components(&id).into_iter()
+                    .filter_map(|comp_id| world.serialize_component(&id, &comp_id).map(|data| (comp_id, data)))
+                    .collect(),
+            }).collect(),
+            id_gen: id_gen.clone(),
+        }
+    }
+
+    // rebuild the snapshot's world on top of a world that has the same info, ex: one made from
+    // the world template
+    pub fn make_world(&self, base: &World) -> Result<World, WorldError> {
+        let mut world = base.clone();
+        for cid in world.characters.clone() {
+            world.erase_character(&cid)?;
+        }
+        for update in &self.characters {
+            update.update_character(&mut world)?;
+        }
+        world.tick = self.tick;
+        Ok(world)
+    }
+}
+
+impl GetCommandID for WorldSnapshot {
+    fn command_id(&self) -> crate::model::commands::CommandID {
+        crate::model::commands::CommandID::WorldSnapshot
+    }
+}
+
+// sent on connect: the server's character definitions and the map it is running
+// clients whose definitions or map don't match would simulate a different world, so they disconnect,
+// and the server refuses their login in case they don't
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Handshake {
+    pub definitions_hash: u64,
+    pub map: String,
+    pub map_hash: u64,
+}
+
+impl GetCommandID for Handshake {
+    fn command_id(&self) -> crate::model::commands::CommandID {
+        crate::model::commands::CommandID::Handshake
+    }
+}
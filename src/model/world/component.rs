@@ -1,9 +1,9 @@
-use std::{collections::HashMap, fmt::{Display, Debug}};
+use std::{collections::{HashMap, HashSet}, fmt::{Display, Debug}, hash::Hasher};
 
 use itertools::Itertools;
 use serde::{Serialize, Deserialize, de::DeserializeOwned};
 
-use super::{character::CharacterID, WorldError, system::{base::CharacterBaseUpdate, projectile::ProjectileUpdate, status::StatusUpdate, movement::Movement, auto_attack::AutoAttackUpdate, flash::FlashUpdate}, system::{health::CharacterHealthUpdate, collision::CollisionUpdate}, WorldErrorI};
+use super::{character::CharacterID, hash::StateHasher, WorldError, system::{base::CharacterBaseUpdate, projectile::ProjectileUpdate, status::StatusUpdate, movement::Movement, auto_attack::AutoAttackUpdate, flash::FlashUpdate}, system::{health::CharacterHealthUpdate, collision::CollisionUpdate, death::DeathUpdate, modifiers::ModifiersUpdate, aoe::AoeUpdate, bolt::BoltUpdate, mana::ManaUpdate, team::CharacterTeamUpdate, experience::ExperienceUpdate, gold::GoldUpdate, inventory::InventoryUpdate}, WorldErrorI};
 use strum_macros::EnumIter;
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy, EnumIter)]
@@ -18,6 +18,16 @@ pub enum ComponentID {
     Status,
     Flash,
     Collision,
+    Death,
+    Modifiers,
+    Aoe,
+    Bolt,
+    Mana,
+    Team,
+    Tower,
+    Experience,
+    Gold,
+    Inventory,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -30,8 +40,18 @@ pub enum ComponentUpdateData {
     AutoAttack(AutoAttackUpdate),
     Flash(FlashUpdate),
     Collision(CollisionUpdate),
+    Death(DeathUpdate),
+    Modifiers(ModifiersUpdate),
+    Aoe(AoeUpdate),
+    Bolt(BoltUpdate),
+    Mana(ManaUpdate),
+    Team(CharacterTeamUpdate),
+    Experience(ExperienceUpdate),
+    Gold(GoldUpdate),
+    Inventory(InventoryUpdate),
     CasterMinion,
     IceWiz,
+    Tower,
 }
 
 impl ComponentUpdateData {
@@ -47,6 +67,16 @@ impl ComponentUpdateData {
             ComponentUpdateData::CasterMinion => ComponentID::CasterMinion,
             ComponentUpdateData::IceWiz => ComponentID::IceWiz,
             ComponentUpdateData::Collision(_) => ComponentID::Collision,
+            ComponentUpdateData::Death(_) => ComponentID::Death,
+            ComponentUpdateData::Modifiers(_) => ComponentID::Modifiers,
+            ComponentUpdateData::Aoe(_) => ComponentID::Aoe,
+            ComponentUpdateData::Bolt(_) => ComponentID::Bolt,
+            ComponentUpdateData::Mana(_) => ComponentID::Mana,
+            ComponentUpdateData::Team(_) => ComponentID::Team,
+            ComponentUpdateData::Tower => ComponentID::Tower,
+            ComponentUpdateData::Experience(_) => ComponentID::Experience,
+            ComponentUpdateData::Gold(_) => ComponentID::Gold,
+            ComponentUpdateData::Inventory(_) => ComponentID::Inventory,
         }
     }
 }
@@ -57,6 +87,12 @@ pub struct ComponentUpdate {
     pub data: ComponentUpdateData,
 }
 
+// add up the changes made to a value in one tick
+// summed in a fixed order so every world gets the same result, whatever order the changes came in
+pub fn sum_changes(changes: impl Iterator<Item = f32>) -> Option<f32> {
+    changes.sorted_by(f32::total_cmp).reduce(|a, b| a + b)
+}
+
 impl Display for ComponentID {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:?}", self)
@@ -79,6 +115,8 @@ pub trait ComponentStorageCommon {
     fn deserialize_insert(&mut self, cid: &CharacterID, data: Vec<u8>);
     fn erase(&mut self, cid: &CharacterID);
     fn update(&mut self, updates: &[ComponentUpdate]) -> Result<(), Vec<WorldError>>;
+    fn state_hash(&self, hasher: &mut StateHasher, characters: &HashSet<CharacterID>);
+    fn to_json(&self, cid: &CharacterID) -> Option<serde_json::Value>;
 }
 
 pub trait ComponentStorageContainer<T: Sized + Serialize>: ComponentStorageCommon {
@@ -177,6 +215,18 @@ impl<T: Serialize + GetComponentID + DeserializeOwned + Component + Debug> Compo
         // }
         Ok(())
     }
+    fn state_hash(&self, hasher: &mut StateHasher, characters: &HashSet<CharacterID>) {
+        // hash in character ID order so the result doesn't depend on HashMap iteration order
+        for cid in self.components.keys().filter(|cid| characters.contains(cid)).sorted() {
+            hasher.write_num(cid.get_num());
+            if let Some(data) = self.serialize(cid) {
+                hasher.write(data.as_slice());
+            }
+        }
+    }
+    fn to_json(&self, cid: &CharacterID) -> Option<serde_json::Value> {
+        self.components.get(cid).and_then(|component| serde_json::to_value(component).ok())
+    }
 }
 
 impl<T> ComponentStorageContainer<T> for ComponentStorage<T>
@@ -0,0 +1,465 @@
+use std::{fs, hash::Hasher, path::Path, collections::{BTreeSet, BTreeMap}};
+use nalgebra::Vector3;
+use serde::{Serialize, Deserialize};
+
+use super::{
+    character::CharacterType,
+    component::ComponentID,
+    hash::StateHasher,
+    vision::VisionInfo,
+    system::{base::{CharacterBase, CharacterFlip}, health::CharacterHealth, auto_attack::AutoAttackInfo, flash::FlashInfo, collision::{Collision, Collider, Layer}, death::DeathInfo, aoe::AoeInfo, bolt::BoltInfo, health::DamageKind, status::CrowdControl, ability::AbilityInfo, mana::ManaInfo, experience::{ExperienceInfo, BountyInfo}, modifiers::{Stat, ModifierValue}, gold::GoldInfo, inventory::ItemInfo},
+    WorldInfo, WorldError, WorldErrorI,
+};
+
+pub const DEFINITIONS_PATH: &str = "definitions";
+
+// inside the definitions directory, so it isn't read as a character
+pub const SHOP_PATH: &str = "shop/shop.json";
+
+// stats for one character type, loaded from a json file in the definitions directory
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CharacterDefinition {
+    pub ctype: CharacterType,
+    pub base: BaseDefinition,
+    pub health: f32,
+    #[serde(default)]
+    pub auto_attack: Option<AutoAttackDefinition>,
+    #[serde(default)]
+    pub flash: Option<FlashDefinition>,
+    #[serde(default)]
+    pub aoe: Option<AoeDefinition>,
+    #[serde(default)]
+    pub bolt: Option<BoltDefinition>,
+    #[serde(default)]
+    pub collision: Option<CollisionDefinition>,
+    #[serde(default)]
+    pub death: Option<DeathDefinition>,
+    #[serde(default)]
+    pub mana: Option<ManaDefinition>,
+    #[serde(default)]
+    pub experience: Option<ExperienceDefinition>,
+    #[serde(default)]
+    pub bounty: Option<BountyDefinition>,
+    #[serde(default)]
+    pub gold: Option<GoldDefinition>,
+    #[serde(default)]
+    pub vision: Option<VisionDefinition>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BaseDefinition {
+    pub center_offset: Vector3<f32>,
+    pub speed: f32,
+    pub attack_damage: f32,
+    pub range: f32,
+    pub attack_speed: f32,
+    #[serde(default)]
+    pub armor: f32,
+    #[serde(default)]
+    pub magic_resist: f32,
+    pub targetable: bool,
+}
+
+// see AutoAttackInfo::init for what each time means
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AutoAttackDefinition {
+    pub wind_up_time: f32,
+    pub casting_time: f32,
+    pub wind_down_time: f32,
+    pub fire_time: f32,
+    pub projectile_speed: f32,
+    pub projectile_offset: Vector3<f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FlashDefinition {
+    pub duration: f32,
+    pub cooldown: f32,
+    pub wind_up_time: f32,
+    pub casting_time: f32,
+    pub wind_down_time: f32,
+    pub fire_time: f32,
+    pub range: f32,
+    #[serde(default)]
+    pub cost: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AoeDefinition {
+    pub duration: f32,
+    pub cooldown: f32,
+    pub wind_up_time: f32,
+    pub casting_time: f32,
+    pub wind_down_time: f32,
+    pub fire_time: f32,
+    pub range: f32,
+    pub radius: f32,
+    #[serde(default)]
+    pub delay: f32, // goes off right when fired by default
+    pub damage: f32,
+    pub kind: DamageKind,
+    #[serde(default)]
+    pub effect: Option<CrowdControl>,
+    #[serde(default)]
+    pub effect_duration: f32,
+    #[serde(default)]
+    pub cost: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BoltDefinition {
+    pub duration: f32,
+    pub cooldown: f32,
+    pub wind_up_time: f32,
+    pub casting_time: f32,
+    pub wind_down_time: f32,
+    pub fire_time: f32,
+    pub range: f32,
+    pub radius: f32,
+    pub speed: f32,
+    pub damage: f32,
+    pub kind: DamageKind,
+    pub projectile_offset: Vector3<f32>,
+    pub layers: Vec<Layer>, // what it can hit
+    #[serde(default)]
+    pub cost: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CollisionDefinition {
+    pub radius: f32, // 0 for a point collider
+    pub layer: Option<Layer>,
+    pub against: Vec<Layer>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeathDefinition {
+    #[serde(default)]
+    pub respawn_time: Option<f32>, // removed after the cleanup time if it doesn't respawn
+    pub cleanup_time: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VisionDefinition {
+    pub radius: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ManaDefinition {
+    pub max_mana: f32,
+    pub regen: f32, // per second
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExperienceDefinition {
+    pub thresholds: Vec<f32>, // total xp needed for level 2, 3, ...
+    #[serde(default)]
+    pub growth: BTreeMap<Stat, f32>, // per level
+    #[serde(default)]
+    pub health_growth: f32, // per level
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BountyDefinition {
+    #[serde(default)]
+    pub xp: f32,
+    pub radius: f32,
+    #[serde(default)]
+    pub gold: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GoldDefinition {
+    pub starting: f32,
+    pub income: f32, // per second
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ItemModifierDefinition {
+    pub stat: Stat,
+    pub value: ModifierValue,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ItemDefinition {
+    pub name: String,
+    pub cost: f32,
+    pub modifiers: Vec<ItemModifierDefinition>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ShopDefinition {
+    pub items: Vec<ItemDefinition>,
+}
+
+impl ShopDefinition {
+    pub fn world_info(&self) -> Result<WorldInfo, WorldError> {
+        let invalid = |reason: String| WorldErrorI::InvalidDefinitionFile(SHOP_PATH.to_string(), reason).err();
+        let mut info = WorldInfo::new();
+        for item in &self.items {
+            let values_valid = item.modifiers.iter().all(|modifier| match modifier.value {
+                ModifierValue::Flat(value) | ModifierValue::Percent(value) => value.is_finite(),
+            });
+            if !(item.cost.is_finite() && item.cost >= 0.0 && values_valid) {
+                return Err(invalid(format!("Invalid item {}", item.name)));
+            }
+            if info.items.contains_key(&item.name) {
+                return Err(invalid(format!("Item {} is defined more than once", item.name)));
+            }
+            info.items.insert(item.name.clone(), ItemInfo {
+                cost: item.cost,
+                modifiers: item.modifiers.iter().map(|modifier| (modifier.stat, modifier.value)).collect(),
+            });
+        }
+        Ok(info)
+    }
+}
+
+fn valid(ctype: CharacterType, comp_id: ComponentID, values: &[f32]) -> Result<(), WorldError> {
+    match values.iter().all(|value| value.is_finite() && *value >= 0.0) {
+        true => Ok(()),
+        false => Err(WorldErrorI::InvalidComponentInfo(ctype, comp_id).err()),
+    }
+}
+
+impl CharacterDefinition {
+    pub fn world_info(&self) -> Result<WorldInfo, WorldError> {
+        let ctype = self.ctype;
+        let mut info = WorldInfo::new();
+
+        let base = &self.base;
+        valid(ctype, ComponentID::Base, &[base.speed, base.attack_damage, base.range, base.attack_speed, base.armor, base.magic_resist])?;
+        if !base.center_offset.iter().all(|x| x.is_finite()) {
+            return Err(WorldErrorI::InvalidComponentInfo(ctype, ComponentID::Base).err());
+        }
+        info.base.insert(ctype, CharacterBase {
+            ctype,
+            position: Vector3::new(0.0, 0.0, 0.0),
+            center_offset: base.center_offset,
+            speed: base.speed,
+            attack_damage: base.attack_damage,
+            range: base.range,
+            attack_speed: base.attack_speed,
+            armor: base.armor,
+            magic_resist: base.magic_resist,
+            flip: CharacterFlip::Right,
+            targetable: base.targetable,
+        });
+
+        valid(ctype, ComponentID::Health, &[self.health])?;
+        if self.health <= 0.0 {
+            return Err(WorldErrorI::InvalidComponentInfo(ctype, ComponentID::Health).err());
+        }
+        info.health.insert(ctype, CharacterHealth {
+            health: self.health,
+            max_health: self.health,
+        });
+
+        if let Some(aa) = &self.auto_attack {
+            valid(ctype, ComponentID::AutoAttack, &[aa.wind_up_time, aa.casting_time, aa.wind_down_time, aa.fire_time, aa.projectile_speed])?;
+            info.auto_attack.insert(ctype, AutoAttackInfo::init(
+                ctype,
+                aa.wind_up_time,
+                aa.casting_time,
+                aa.wind_down_time,
+                aa.fire_time,
+                aa.projectile_speed,
+                aa.projectile_offset
+            )?);
+        }
+
+        if let Some(flash) = &self.flash {
+            valid(ctype, ComponentID::Flash, &[flash.duration, flash.cooldown, flash.wind_up_time, flash.casting_time, flash.wind_down_time, flash.fire_time, flash.range, flash.cost])?;
+            let mut flash_info = FlashInfo::init(
+                flash.duration,
+                flash.cooldown,
+                flash.wind_up_time,
+                flash.casting_time,
+                flash.wind_down_time,
+                flash.fire_time,
+                flash.range
+            ).map_err(|_| WorldErrorI::InvalidComponentInfo(ctype, ComponentID::Flash).err())?;
+            flash_info.ability.cost = flash.cost;
+            info.flash.insert(ctype, flash_info);
+        }
+
+        if let Some(aoe) = &self.aoe {
+            let slow = match aoe.effect {
+                Some(CrowdControl::Slow(amount)) => amount,
+                _ => 0.0,
+            };
+            valid(ctype, ComponentID::Aoe, &[aoe.duration, aoe.cooldown, aoe.wind_up_time, aoe.casting_time, aoe.wind_down_time, aoe.fire_time, aoe.range, aoe.radius, aoe.delay, aoe.damage, aoe.effect_duration, aoe.cost, slow, 1.0 - slow])?;
+            info.aoe.insert(ctype, AoeInfo {
+                ability: AbilityInfo {
+                    cost: aoe.cost,
+                    ..AbilityInfo::new(aoe.wind_up_time, aoe.casting_time, aoe.wind_down_time, aoe.fire_time)
+                        .map_err(|_| WorldErrorI::InvalidComponentInfo(ctype, ComponentID::Aoe).err())?
+                },
+                range: aoe.range,
+                radius: aoe.radius,
+                delay: aoe.delay,
+                duration: aoe.duration,
+                cooldown: aoe.cooldown,
+                damage: aoe.damage,
+                kind: aoe.kind,
+                effect: aoe.effect.map(|cc| (cc, aoe.effect_duration)),
+            });
+        }
+
+        if let Some(bolt) = &self.bolt {
+            valid(ctype, ComponentID::Bolt, &[bolt.duration, bolt.cooldown, bolt.wind_up_time, bolt.casting_time, bolt.wind_down_time, bolt.fire_time, bolt.range, bolt.radius, bolt.speed, bolt.damage, bolt.cost])?;
+            info.bolt.insert(ctype, BoltInfo {
+                ability: AbilityInfo {
+                    cost: bolt.cost,
+                    ..AbilityInfo::new(bolt.wind_up_time, bolt.casting_time, bolt.wind_down_time, bolt.fire_time)
+                        .map_err(|_| WorldErrorI::InvalidComponentInfo(ctype, ComponentID::Bolt).err())?
+                },
+                range: bolt.range,
+                radius: bolt.radius,
+                speed: bolt.speed,
+                duration: bolt.duration,
+                cooldown: bolt.cooldown,
+                damage: bolt.damage,
+                kind: bolt.kind,
+                projectile_offset: bolt.projectile_offset,
+                layers: bolt.layers.iter().copied().collect::<BTreeSet<Layer>>(),
+            });
+        }
+
+        if let Some(mana) = &self.mana {
+            valid(ctype, ComponentID::Mana, &[mana.max_mana, mana.regen])?;
+            info.mana.insert(ctype, ManaInfo {
+                max_mana: mana.max_mana,
+                regen: mana.regen,
+            });
+        }
+
+        if let Some(experience) = &self.experience {
+            valid(ctype, ComponentID::Experience, &experience.thresholds)?;
+            valid(ctype, ComponentID::Experience, &[experience.health_growth])?;
+            // growth can be negative, ex: a champion that slows down as it levels
+            if !experience.growth.values().all(|growth| growth.is_finite())
+                || !experience.thresholds.windows(2).all(|pair| pair[0] <= pair[1]) {
+                return Err(WorldErrorI::InvalidComponentInfo(ctype, ComponentID::Experience).err());
+            }
+            info.experience.insert(ctype, ExperienceInfo {
+                thresholds: experience.thresholds.clone(),
+                growth: experience.growth.clone(),
+                health_growth: experience.health_growth,
+            });
+        }
+
+        if let Some(bounty) = &self.bounty {
+            valid(ctype, ComponentID::Experience, &[bounty.xp, bounty.radius, bounty.gold])?;
+            info.bounty.insert(ctype, BountyInfo {
+                xp: bounty.xp,
+                radius: bounty.radius,
+                gold: bounty.gold,
+            });
+        }
+
+        if let Some(gold) = &self.gold {
+            valid(ctype, ComponentID::Gold, &[gold.starting, gold.income])?;
+            info.gold.insert(ctype, GoldInfo {
+                starting: gold.starting,
+                income: gold.income,
+            });
+        }
+
+        if let Some(vision) = &self.vision {
+            valid(ctype, ComponentID::Base, &[vision.radius])?;
+            info.vision.insert(ctype, VisionInfo {
+                radius: vision.radius,
+            });
+        }
+
+        if let Some(collision) = &self.collision {
+            valid(ctype, ComponentID::Collision, &[collision.radius])?;
+            info.collision.insert(ctype, Collision {
+                collider: match collision.radius {
+                    radius if radius > 0.0 => Collider::Circle(radius),
+                    _ => Collider::Point,
+                },
+                layer: collision.layer,
+                against: collision.against.iter().copied().collect::<BTreeSet<Layer>>(),
+            });
+        }
+
+        if let Some(death) = &self.death {
+            valid(ctype, ComponentID::Death, &[death.respawn_time.unwrap_or(0.0), death.cleanup_time])?;
+            info.death.insert(ctype, DeathInfo {
+                respawn_time: death.respawn_time,
+                cleanup_time: death.cleanup_time,
+            });
+        }
+        Ok(info)
+    }
+}
+
+// every character definition and the shop, along with a hash of their contents so that the client
+// and server can check that they agree
+#[derive(Debug, Clone)]
+pub struct Definitions {
+    pub characters: Vec<CharacterDefinition>,
+    pub shop: ShopDefinition,
+    pub hash: u64,
+}
+
+impl Definitions {
+    pub fn load(dir: &str) -> Result<Self, WorldError> {
+        let read_error = |path: &Path, err: String| WorldErrorI::InvalidDefinitionFile(path.display().to_string(), err).err();
+        let mut paths = vec![];
+        for entry in fs::read_dir(dir).map_err(|err| read_error(Path::new(dir), err.to_string()))? {
+            let path = entry.map_err(|err| read_error(Path::new(dir), err.to_string()))?.path();
+            if path.extension().map(|ext| ext == "json").unwrap_or(false) {
+                paths.push(path);
+            }
+        }
+        // sorted so that the hash doesn't depend on the directory listing order
+        paths.sort();
+
+        let mut characters: Vec<CharacterDefinition> = vec![];
+        for path in paths {
+            let data = fs::read_to_string(&path).map_err(|err| read_error(&path, err.to_string()))?;
+            let def: CharacterDefinition = serde_json::from_str(data.as_str())
+                .map_err(|err| read_error(&path, err.to_string()))?;
+            if characters.iter().any(|other| other.ctype == def.ctype) {
+                return Err(read_error(&path, format!("{:?} is defined more than once", def.ctype)));
+            }
+            characters.push(def);
+        }
+
+        // without a shop file there is nothing to buy
+        let shop_path = Path::new(dir).join(SHOP_PATH);
+        let shop: ShopDefinition = match shop_path.exists() {
+            true => {
+                let data = fs::read_to_string(&shop_path).map_err(|err| read_error(&shop_path, err.to_string()))?;
+                serde_json::from_str(data.as_str()).map_err(|err| read_error(&shop_path, err.to_string()))?
+            },
+            false => ShopDefinition::default(),
+        };
+
+        // hash the parsed definitions rather than the files, so formatting doesn't matter
+        let mut hasher = StateHasher::new();
+        for def in &characters {
+            let data = bincode::serialize(def).map_err(|err| WorldErrorI::InvalidDefinitionFile(dir.to_string(), err.to_string()).err())?;
+            hasher.write(data.as_slice());
+        }
+        let data = bincode::serialize(&shop).map_err(|err| read_error(&shop_path, err.to_string()))?;
+        hasher.write(data.as_slice());
+        let definitions = Self {
+            characters,
+            shop,
+            hash: hasher.finish(),
+        };
+        // invalid values are reported here, so that nothing starts with characters missing their info
+        definitions.world_info()?;
+        Ok(definitions)
+    }
+
+    pub fn world_info(&self) -> Result<Vec<WorldInfo>, WorldError> {
+        self.characters.iter().map(|def| def.world_info())
+            .chain(Some(self.shop.world_info()))
+            .collect()
+    }
+}
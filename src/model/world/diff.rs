@@ -0,0 +1,98 @@
+use std::{collections::HashMap, fmt::Display};
+
+use serde_json::Value;
+use strum::IntoEnumIterator;
+
+use super::{character::CharacterID, component::ComponentID};
+
+// a single differing value inside a component, found by walking both serialized components
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDiff {
+    pub path: String,
+    pub this: Value,
+    pub other: Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ComponentDiff {
+    Added(CharacterID), // only the other world has this component
+    Removed(CharacterID), // only this world has this component
+    Changed(CharacterID, Vec<FieldDiff>),
+}
+
+// structural difference between two worlds, from the point of view of the first world
+// ex: added characters are characters that only exist in the other world
+#[derive(Debug, Clone, Default)]
+pub struct WorldDiff {
+    pub added: Vec<CharacterID>,
+    pub removed: Vec<CharacterID>,
+    pub components: HashMap<ComponentID, Vec<ComponentDiff>>,
+}
+
+impl WorldDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.components.is_empty()
+    }
+}
+
+impl Display for WorldDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences");
+        }
+        if !self.added.is_empty() {
+            writeln!(f, "Characters added: {:?}", self.added)?;
+        }
+        if !self.removed.is_empty() {
+            writeln!(f, "Characters removed: {:?}", self.removed)?;
+        }
+        for comp_id in ComponentID::iter() {
+            let changes = match self.components.get(&comp_id) {
+                Some(changes) => changes,
+                None => continue,
+            };
+            writeln!(f, "{}:", comp_id)?;
+            for change in changes {
+                match change {
+                    ComponentDiff::Added(cid) => writeln!(f, "  {:?}: added", cid)?,
+                    ComponentDiff::Removed(cid) => writeln!(f, "  {:?}: removed", cid)?,
+                    ComponentDiff::Changed(cid, fields) => for field in fields {
+                        let path = if field.path.is_empty() { "(value)" } else { field.path.as_str() };
+                        writeln!(f, "  {:?} {}: {} -> {}", cid, path, field.this, field.other)?;
+                    },
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// recursively compare two serialized values, recording the path of every leaf that differs
+pub fn diff_values(path: &str, this: &Value, other: &Value, diffs: &mut Vec<FieldDiff>) {
+    match (this, other) {
+        (Value::Object(this_map), Value::Object(other_map)) => {
+            // serde_json maps are sorted by key, so this is deterministic
+            for (key, this_value) in this_map {
+                let field_path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                match other_map.get(key) {
+                    Some(other_value) => diff_values(field_path.as_str(), this_value, other_value, diffs),
+                    None => diffs.push(FieldDiff { path: field_path, this: this_value.clone(), other: Value::Null }),
+                }
+            }
+            for (key, other_value) in other_map {
+                if !this_map.contains_key(key) {
+                    let field_path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                    diffs.push(FieldDiff { path: field_path, this: Value::Null, other: other_value.clone() });
+                }
+            }
+        },
+        (Value::Array(this_vec), Value::Array(other_vec)) if this_vec.len() == other_vec.len() => {
+            for (i, (this_value, other_value)) in this_vec.iter().zip(other_vec.iter()).enumerate() {
+                diff_values(format!("{}[{}]", path, i).as_str(), this_value, other_value, diffs);
+            }
+        },
+        _ => if this != other {
+            diffs.push(FieldDiff { path: path.to_string(), this: this.clone(), other: other.clone() });
+        },
+    }
+}
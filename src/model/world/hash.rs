@@ -0,0 +1,39 @@
+use std::hash::Hasher;
+
+// FNV-1a hasher used for world state hashes
+// DefaultHasher isn't used since its algorithm can change between std versions, and the client and
+// server need to agree on the hash even when they were built separately
+pub struct StateHasher(u64);
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+impl StateHasher {
+    pub fn new() -> Self {
+        Self(FNV_OFFSET_BASIS)
+    }
+
+    // integers are always written little endian so that the hash doesn't depend on the platform
+    pub fn write_num(&mut self, num: u64) {
+        self.write(&num.to_le_bytes());
+    }
+}
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hasher for StateHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+}
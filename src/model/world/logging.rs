@@ -1,6 +1,6 @@
 use std::{fs::{File, OpenOptions}, io::{self, Write}};
 
-use super::{World, WorldError, WorldErrorI};
+use super::{World, WorldError, WorldErrorI, WorldTick, character::CharacterID, diff::WorldDiff};
 
 
 pub struct Logger {
@@ -26,3 +26,23 @@ impl Logger {
         self.file.flush().map_err(|_| println!("Error flushing log file")).ok();
     }
 }
+
+// writes a readable report whenever a FixWorld disagrees with the local world
+pub struct DesyncReport {
+    file: File
+}
+
+impl DesyncReport {
+    pub fn init(file_name: &str) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(file_name)?;
+        Ok(Self {
+            file
+        })
+    }
+
+    pub fn report(&mut self, tick: WorldTick, cid: &CharacterID, diff: &WorldDiff) {
+        writeln!(self.file, "Tick {}, FixWorld for {:?} disagrees with local state:\n{}", tick, cid, diff)
+            .map_err(|_| println!("Error writing to desync report")).ok();
+        self.file.flush().map_err(|_| println!("Error flushing desync report")).ok();
+    }
+}
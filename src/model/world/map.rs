@@ -0,0 +1,166 @@
+use std::{fs, hash::Hasher, path::{Path, PathBuf}};
+use nalgebra::Vector2;
+use serde::{Serialize, Deserialize};
+
+use super::{
+    character::CharacterType,
+    hash::StateHasher,
+    system::{collision::CollisionInfo, ground::GroundGrid, inventory::ShopZone},
+    WorldInfo, WorldError, WorldErrorI,
+};
+
+pub const MAPS_DIR: &str = "maps";
+
+// the map the server runs when none is chosen, and the client starts with before it connects
+pub const DEFAULT_MAP: &str = "default";
+
+// inside a map's directory
+pub const MANIFEST_FILE: &str = "map.json";
+
+// an image drawn over the map, tiled with a texture wherever its mask is opaque
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TerrainLayer {
+    pub name: String,
+    pub mask: String, // image in the map directory
+    pub texture: String,
+    pub tiles: f32, // times the texture repeats across the map
+    pub speed: f32, // movement multiplier on the layer's ground, 0 can't be walked on
+}
+
+// waypoints go from the first team's side of the map to the second team's side
+// the second team walks them in reverse
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Lane {
+    pub name: String,
+    pub waypoints: Vec<Vector2<f32>>,
+}
+
+// a tower that guards one side of the map, placed once when the world starts
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TowerPlacement {
+    pub side: usize, // 0 for the first team, 1 for the second
+    pub position: Vector2<f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ZoneDefinition {
+    pub position: Vector2<f32>,
+    pub radius: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WaveMember {
+    pub ctype: CharacterType,
+    pub count: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WaveSchedule {
+    pub first_wave: f32, // seconds from the start of the world
+    pub interval: f32, // seconds between waves
+    pub spacing: f32, // distance between the members of a wave, in the order they are listed
+    pub wave: Vec<WaveMember>,
+}
+
+// the map.json at the root of a map's directory
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MapManifest {
+    pub name: String,
+    pub world_size: f32, // the images cover this many world units, centered on the origin
+    pub collision: String, // image in the map directory, opaque pixels are terrain
+    pub layers: Vec<TerrainLayer>, // drawn in order
+    pub spawns: Vec<Vector2<f32>>, // one per side of the map
+    #[serde(default)]
+    pub lanes: Vec<Lane>,
+    #[serde(default)]
+    pub towers: Vec<TowerPlacement>,
+    #[serde(default)]
+    pub shops: Vec<ZoneDefinition>, // fountains, where characters can buy and sell
+    pub waves: WaveSchedule,
+}
+
+// a loaded map directory, along with a hash of everything in it that changes the simulation so
+// that the client and server can check that they agree
+#[derive(Debug, Clone)]
+pub struct MapPackage {
+    pub id: String,
+    pub dir: PathBuf,
+    pub manifest: MapManifest,
+    pub collision: CollisionInfo,
+    pub hash: u64,
+}
+
+impl MapPackage {
+    pub fn load(id: &str) -> Result<Self, WorldError> {
+        let dir = Path::new(MAPS_DIR).join(id);
+        let manifest_path = dir.join(MANIFEST_FILE);
+        let invalid = |reason: String| WorldErrorI::InvalidDefinitionFile(manifest_path.display().to_string(), reason).err();
+        // the id comes from the server, so it can't be allowed to point outside the maps directory
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(invalid(format!("Invalid map id {:?}", id)));
+        }
+        let data = fs::read_to_string(&manifest_path).map_err(|err| invalid(err.to_string()))?;
+        let manifest: MapManifest = serde_json::from_str(data.as_str()).map_err(|err| invalid(err.to_string()))?;
+
+        if !(manifest.world_size.is_finite() && manifest.world_size > 0.0) {
+            return Err(invalid("Invalid world size".to_string()));
+        }
+        if manifest.spawns.len() < 2 || !manifest.spawns.iter().all(|spawn| spawn.iter().all(|x| x.is_finite())) {
+            return Err(invalid("Expected a spawn point for each side".to_string()));
+        }
+        if let Some(layer) = manifest.layers.iter().find(|layer| !(layer.tiles.is_finite() && layer.tiles > 0.0)) {
+            return Err(invalid(format!("Invalid tile count for layer {}", layer.name)));
+        }
+        if let Some(layer) = manifest.layers.iter().find(|layer| !(layer.speed.is_finite() && layer.speed >= 0.0)) {
+            return Err(invalid(format!("Invalid speed for layer {}", layer.name)));
+        }
+        if let Some(lane) = manifest.lanes.iter().find(|lane| lane.waypoints.is_empty()) {
+            return Err(invalid(format!("Lane {} has no waypoints", lane.name)));
+        }
+        if manifest.towers.iter().any(|tower| tower.side > 1) {
+            return Err(invalid("Tower is on a side other than 0 or 1".to_string()));
+        }
+        if let Some(zone) = manifest.shops.iter().find(|zone| !(zone.position.iter().all(|x| x.is_finite()) && zone.radius.is_finite() && zone.radius >= 0.0)) {
+            return Err(invalid(format!("Invalid shop zone at {:?}", zone.position)));
+        }
+        let waves = &manifest.waves;
+        if !(waves.first_wave >= 0.0 && waves.interval > 0.0 && waves.spacing >= 0.0) {
+            return Err(invalid("Invalid wave timing".to_string()));
+        }
+
+        let layers: Vec<(PathBuf, f32)> = manifest.layers.iter()
+            .map(|layer| (dir.join(&layer.mask), layer.speed))
+            .collect();
+        let ground = GroundGrid::load(manifest.world_size, &layers).map_err(invalid)?;
+        let collision_path = dir.join(&manifest.collision);
+        let collision = CollisionInfo::load(&collision_path, manifest.world_size, ground)
+            .map_err(|err| WorldErrorI::InvalidDefinitionFile(collision_path.display().to_string(), err).err())?;
+
+        // the parsed manifest and every image it refers to, except the textures which are only drawn
+        let mut hasher = StateHasher::new();
+        let data = bincode::serialize(&manifest).map_err(|err| invalid(err.to_string()))?;
+        hasher.write(data.as_slice());
+        for file in std::iter::once(&manifest.collision).chain(manifest.layers.iter().map(|layer| &layer.mask)) {
+            let path = dir.join(file);
+            let data = fs::read(&path).map_err(|err| WorldErrorI::InvalidDefinitionFile(path.display().to_string(), err.to_string()).err())?;
+            hasher.write(data.as_slice());
+        }
+
+        Ok(Self {
+            id: id.to_string(),
+            dir,
+            manifest,
+            collision,
+            hash: hasher.finish(),
+        })
+    }
+
+    pub fn world_info(&self) -> WorldInfo {
+        let mut info = WorldInfo::new();
+        info.spawns = self.manifest.spawns.clone();
+        info.shop_zones = self.manifest.shops.iter()
+            .map(|zone| ShopZone { position: zone.position, radius: zone.radius })
+            .collect();
+        info
+    }
+}
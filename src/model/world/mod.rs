@@ -1,14 +1,10 @@
 use itertools::Itertools;
-use nalgebra::Vector2;
 use strum::IntoEnumIterator;
-use std::{collections::{HashMap, HashSet}, rc::Rc};
-use serde::{Serialize, de::DeserializeOwned};
+use std::{collections::{HashMap, HashSet}, rc::Rc, hash::Hasher};
+use nalgebra::Vector2;
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
 
 use self::{
-//    player::{
-//        TeamID,
-//        Team, PlayerData
-//    },
     character::{CharacterID, CharacterType},
     component::{
         ComponentStorage,
@@ -38,13 +34,26 @@ use self::{
             CasterMinion,
             self, CasterMinionSystem
         },
+        tower::{
+            Tower,
+            self, TowerSystem
+        },
         base::{
             CharacterBase,
             BaseSystem
         },
-        health::{CharacterHealth, HealthSystem},
+        health::{CharacterHealth, HealthSystem, DamageEvent},
         status::{StatusSystem, StatusComponent}, flash::{Flash, FlashInfo, FlashAbilitySystem}, collision::{Collision, CollisionSystem, CollisionInfo},
-    }, template::WorldTemplate
+        death::{Death, DeathInfo, DeathSystem},
+        modifiers::{Modifiers, ModifiersSystem},
+        aoe::{Aoe, AoeInfo, AoeAbilitySystem},
+        bolt::{Bolt, BoltInfo, BoltAbilitySystem},
+        mana::{Mana, ManaInfo, ManaSystem},
+        team::{CharacterTeam, TeamSystem},
+        experience::{Experience, ExperienceInfo, BountyInfo, ExperienceSystem},
+        gold::{Gold, GoldInfo, GoldSystem},
+        inventory::{Inventory, ItemInfo, ShopZone, InventorySystem},
+    }, template::WorldTemplate, vision::VisionInfo, hash::StateHasher, diff::{WorldDiff, ComponentDiff}, definitions::Definitions, map::MapPackage
 };
 
 use super::{commands::CommandID, WorldTick};
@@ -56,6 +65,12 @@ pub mod system;
 pub mod commands;
 pub mod logging;
 pub mod template;
+pub mod hash;
+pub mod diff;
+pub mod replication;
+pub mod vision;
+pub mod definitions;
+pub mod map;
 
 #[cfg(feature = "server")]
 pub mod server;
@@ -75,9 +90,17 @@ pub enum WorldErrorI {
     InvalidCommandData(CharacterID, String),
     InvalidCommandMapping,
     OutOfRange(CharacterID), // character whose range we are out of
+    InsufficientMana(CharacterID),
+    AllyTarget(CharacterID), // ally that can't be targeted
+    InsufficientGold(CharacterID),
+    NotInShop(CharacterID),
+    InventoryFull(CharacterID),
+    EmptySlot(CharacterID, usize),
+    UnknownItem(String),
     UnexpectedComponentState(CharacterID, ComponentID, String),
     MissingCharacterInfoComponent(CharacterType, ComponentID),
     InvalidComponentInfo(CharacterType, ComponentID),
+    InvalidDefinitionFile(String, String), // file, reason
     InvalidAttackPhase(CharacterID, AutoAttackPhase),
     NoopCommand,
     IllegalInterrupt(CharacterID),
@@ -126,7 +149,6 @@ pub struct World {
     pub errors: Vec<WorldError>,
 
     pub info: Rc<WorldInfo>,
-    // pub teams: HashMap<TeamID, Team>,
     pub characters: HashSet<CharacterID>,
     // pub players: PlayerData,
 
@@ -136,20 +158,34 @@ pub struct World {
     pub movement: ComponentStorage<Movement>,
     pub status: ComponentStorage<StatusComponent>,
     pub collision: ComponentStorage<Collision>,
+    pub death: ComponentStorage<Death>,
+    pub modifiers: ComponentStorage<Modifiers>,
+    pub team: ComponentStorage<CharacterTeam>,
+    pub experience: ComponentStorage<Experience>,
+    pub gold: ComponentStorage<Gold>,
+    pub inventory: ComponentStorage<Inventory>,
 
     pub icewiz: ComponentStorage<IceWiz>,
     pub caster_minion: ComponentStorage<CasterMinion>,
+    pub tower: ComponentStorage<Tower>,
     pub projectile: ComponentStorage<Projectile>,
 
     // abilities
     pub auto_attack: ComponentStorage<AutoAttack>,
     pub flash: ComponentStorage<Flash>,
+    pub aoe: ComponentStorage<Aoe>,
+    pub bolt: ComponentStorage<Bolt>,
+    pub mana: ComponentStorage<Mana>,
 
     // the tick local to the world, should be 100% in sync between client and server
     pub tick: WorldTick,
+
+    // what happened during the last update
+    pub events: Vec<WorldEvent>,
 }
 
 pub trait WorldSystem {
+    // info built into the system, on top of what Definitions::world_info loads for each character type
     fn init_world_info(&self) -> Result<WorldInfo, WorldError>;
 }
 
@@ -172,6 +208,7 @@ pub trait ComponentSystem: WorldSystem {
 pub enum Update {
     Comp(ComponentUpdate),
     World(WorldUpdate),
+    Event(WorldEvent),
 }
 
 pub fn dbg_updates_sorted(updates: &Vec<Update>) -> String {
@@ -189,6 +226,13 @@ pub enum WorldUpdate {
     RemoveCharacterID(CharacterID),
 }
 
+// things that happened during an update, which don't change the world by themselves
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum WorldEvent {
+    Kill { target: CharacterID, killer: Option<CharacterID> },
+    Damage(DamageEvent),
+}
+
 impl WorldUpdate {
     pub fn apply_update(&self, world: &mut World) -> Result<(), WorldError> {
         use WorldUpdate::*;
@@ -209,6 +253,24 @@ pub struct WorldInfo {
     // abilities
     pub auto_attack: HashMap<CharacterType, AutoAttackInfo>,
     pub flash: HashMap<CharacterType, FlashInfo>,
+    pub aoe: HashMap<CharacterType, AoeInfo>,
+    pub bolt: HashMap<CharacterType, BoltInfo>,
+
+    pub collision: HashMap<CharacterType, Collision>,
+    pub terrain: Option<Rc<CollisionInfo>>,
+    pub death: HashMap<CharacterType, DeathInfo>,
+    pub mana: HashMap<CharacterType, ManaInfo>,
+    pub experience: HashMap<CharacterType, ExperienceInfo>,
+    pub bounty: HashMap<CharacterType, BountyInfo>,
+    pub gold: HashMap<CharacterType, GoldInfo>,
+    pub vision: HashMap<CharacterType, VisionInfo>,
+
+    // the shop isn't tied to a character type
+    pub items: HashMap<String, ItemInfo>,
+
+    // from the map
+    pub shop_zones: Vec<ShopZone>,
+    pub spawns: Vec<Vector2<f32>>, // one per side of the map
 
     pub component_systems: HashMap<ComponentID, Box<dyn ComponentSystem>>,
 }
@@ -220,6 +282,19 @@ impl WorldInfo {
             health: HashMap::new(),
             auto_attack: HashMap::new(),
             flash: HashMap::new(),
+            aoe: HashMap::new(),
+            bolt: HashMap::new(),
+            collision: HashMap::new(),
+            terrain: None,
+            death: HashMap::new(),
+            mana: HashMap::new(),
+            experience: HashMap::new(),
+            bounty: HashMap::new(),
+            gold: HashMap::new(),
+            vision: HashMap::new(),
+            items: HashMap::new(),
+            shop_zones: vec![],
+            spawns: vec![],
             component_systems: HashMap::new(),
         }
     }
@@ -230,6 +305,21 @@ impl WorldInfo {
             combo.health.extend(info.health.into_iter());
             combo.auto_attack.extend(info.auto_attack.into_iter());
             combo.flash.extend(info.flash.into_iter());
+            combo.aoe.extend(info.aoe);
+            combo.bolt.extend(info.bolt);
+            combo.collision.extend(info.collision.into_iter());
+            if info.terrain.is_some() {
+                combo.terrain = info.terrain;
+            }
+            combo.death.extend(info.death);
+            combo.mana.extend(info.mana);
+            combo.experience.extend(info.experience);
+            combo.bounty.extend(info.bounty);
+            combo.gold.extend(info.gold);
+            combo.vision.extend(info.vision);
+            combo.items.extend(info.items);
+            combo.shop_zones.extend(info.shop_zones);
+            combo.spawns.extend(info.spawns);
             combo.component_systems.extend(info.component_systems.into_iter());
         }
         combo.component_systems.extend(systems.into_iter());
@@ -244,7 +334,7 @@ pub enum CommandRunResult {
 }
 
 impl World {
-    pub fn new(collision: CollisionInfo) -> World {
+    pub fn new(map: &MapPackage, definitions: &Definitions) -> World {
         // init each system
         let mut systems = HashMap::new();
         for system in [
@@ -256,13 +346,28 @@ impl World {
             Box::new(BaseSystem) as Box<dyn ComponentSystem>,
             Box::new(StatusSystem) as Box<dyn ComponentSystem>,
             Box::new(HealthSystem) as Box<dyn ComponentSystem>,
-            Box::new(CollisionSystem::new(collision)) as Box<dyn ComponentSystem>,
+            Box::new(CollisionSystem::new(map.collision.clone())) as Box<dyn ComponentSystem>,
             Box::new(FlashAbilitySystem) as Box<dyn ComponentSystem>,
+            Box::new(DeathSystem) as Box<dyn ComponentSystem>,
+            Box::new(ModifiersSystem) as Box<dyn ComponentSystem>,
+            Box::new(AoeAbilitySystem) as Box<dyn ComponentSystem>,
+            Box::new(BoltAbilitySystem) as Box<dyn ComponentSystem>,
+            Box::new(ManaSystem) as Box<dyn ComponentSystem>,
+            Box::new(TeamSystem) as Box<dyn ComponentSystem>,
+            Box::new(TowerSystem) as Box<dyn ComponentSystem>,
+            Box::new(ExperienceSystem) as Box<dyn ComponentSystem>,
+            Box::new(GoldSystem) as Box<dyn ComponentSystem>,
+            Box::new(InventorySystem) as Box<dyn ComponentSystem>,
         ] {
             systems.insert(system.get_component_id(), system);
         }
         let (info, errors): (Vec<WorldInfo>, Vec<WorldError>) = systems.values()
             .map(|system| system.init_world_info())
+            .chain(match definitions.world_info() {
+                Ok(info) => info.into_iter().map(Ok).collect(),
+                Err(err) => vec![Err(err)],
+            })
+            .chain(Some(Ok(map.world_info())))
             .fold((vec![], vec![]), |(mut info, mut errors), res| {
                 match res {
                     Ok(ninfo) => info.push(ninfo),
@@ -282,10 +387,21 @@ impl World {
             auto_attack: ComponentStorage::new(),
             icewiz: ComponentStorage::new(),
             caster_minion: ComponentStorage::new(),
+            tower: ComponentStorage::new(),
+            experience: ComponentStorage::new(),
+            gold: ComponentStorage::new(),
+            inventory: ComponentStorage::new(),
             projectile: ComponentStorage::new(),
             status: ComponentStorage::new(),
             flash: ComponentStorage::new(),
+            aoe: ComponentStorage::new(),
+            bolt: ComponentStorage::new(),
+            mana: ComponentStorage::new(),
             collision: ComponentStorage::new(),
+            death: ComponentStorage::new(),
+            modifiers: ComponentStorage::new(),
+            team: ComponentStorage::new(),
+            events: vec![],
         }
     }
 
@@ -338,6 +454,11 @@ impl World {
                     Update::World(c) => Some(c),
                     _ => None
                 })).collect();
+        let events: Vec<WorldEvent> = update_res.iter()
+            .filter_map(|res| match res {
+                Ok(Update::Event(event)) => Some(event.clone()),
+                _ => None
+            }).collect();
         let mut update_errors = update_res.iter().filter_map(|res| res.clone().err()).collect_vec();
         if !update_res.is_empty() {
             update_errors.push(WorldErrorI::Info(format!("Updates: {:?}", update_res)).err());
@@ -433,17 +554,20 @@ impl World {
             .filter_map(|x| match x { Update::World(x) => Some(x), _ => None })
             .filter_map(|update| update.apply_update(&mut world).err()).collect_vec();
         world.errors.extend(world_update_errors);
+        // removed characters would otherwise get default components back from their last updates
+        let characters = world.characters.clone();
         let change_errors = ComponentID::iter()
             .flat_map(|comp_id| world
                 .get_storage_mut(&comp_id)
                 .update(&reduced.iter()
                     .filter_map(|x| match x { Update::Comp(x) => Some(x), _ => None })
-                    .filter(|update| update.data.component_id() == comp_id)
+                    .filter(|update| update.data.component_id() == comp_id && characters.contains(&update.cid))
                     .cloned()
                     .collect_vec())
                 .err()
                 .unwrap_or_default()).collect_vec();
         world.errors.extend(change_errors);
+        world.events = events;
         world.tick += 1;
         // println!("Changed");
         world
@@ -452,10 +576,10 @@ impl World {
     // character commands cannot return Ok(None) 
     pub fn validate_command(&self, command: &WorldCommand) -> Result<Option<CharacterCommandState>, WorldError> {
         match command {
-            WorldCommand::World(GlobalCommand::CreateCharacter(cid, typ)) => {
+            WorldCommand::World(GlobalCommand::CreateCharacter(cid, typ, _, _)) => {
                 match (self.characters.contains(cid), typ) {
                     (true, _) => Err(WorldErrorI::CharacterIDAlreadyExists(*cid).err()),
-                    (false, CharacterType::IceWiz) | (false, CharacterType::CasterMinion) => Ok(None),
+                    (false, CharacterType::IceWiz) | (false, CharacterType::CasterMinion) | (false, CharacterType::Tower) => Ok(None),
                     _ => Err(WorldErrorI::NotImplemented.err()),
                 }
             },
@@ -472,10 +596,11 @@ impl World {
         for command in commands {
             updates.extend(match command {
                 GlobalCommand::Clear => vec![Err(WorldErrorI::NotImplemented.err())],
-                GlobalCommand::CreateCharacter(id, typ) => match typ {
+                GlobalCommand::CreateCharacter(id, typ, team, position) => match typ {
                     CharacterType::Unknown | CharacterType::Projectile => Err(WorldErrorI::NotImplemented.err()),
-                    CharacterType::IceWiz => icewiz::create(self, &id, Vector2::default()),
-                    CharacterType::CasterMinion => caster_minion::create(self, &id, Vector2::default()),
+                    CharacterType::IceWiz => icewiz::create(self, &id, position, team),
+                    CharacterType::CasterMinion => caster_minion::create(self, &id, position, team),
+                    CharacterType::Tower => tower::create(self, &id, position, team),
                 }
                 .map_or_else(|err| vec![Err(err)], |updates| updates.into_iter().map(Ok).collect())
             })
@@ -551,6 +676,16 @@ impl World {
             ComponentID::Status => &self.status as &dyn ComponentStorageCommon,
             ComponentID::Flash => &self.flash as &dyn ComponentStorageCommon,
             ComponentID::Collision => &self.collision as &dyn ComponentStorageCommon,
+            ComponentID::Death => &self.death as &dyn ComponentStorageCommon,
+            ComponentID::Modifiers => &self.modifiers as &dyn ComponentStorageCommon,
+            ComponentID::Aoe => &self.aoe as &dyn ComponentStorageCommon,
+            ComponentID::Bolt => &self.bolt as &dyn ComponentStorageCommon,
+            ComponentID::Mana => &self.mana as &dyn ComponentStorageCommon,
+            ComponentID::Team => &self.team as &dyn ComponentStorageCommon,
+            ComponentID::Tower => &self.tower as &dyn ComponentStorageCommon,
+            ComponentID::Experience => &self.experience as &dyn ComponentStorageCommon,
+            ComponentID::Gold => &self.gold as &dyn ComponentStorageCommon,
+            ComponentID::Inventory => &self.inventory as &dyn ComponentStorageCommon,
         }
     }
 
@@ -566,6 +701,16 @@ impl World {
             ComponentID::Status => &mut self.status as &mut dyn ComponentStorageCommon,
             ComponentID::Flash => &mut self.flash as &mut dyn ComponentStorageCommon,
             ComponentID::Collision => &mut self.collision as &mut dyn ComponentStorageCommon,
+            ComponentID::Death => &mut self.death as &mut dyn ComponentStorageCommon,
+            ComponentID::Modifiers => &mut self.modifiers as &mut dyn ComponentStorageCommon,
+            ComponentID::Aoe => &mut self.aoe as &mut dyn ComponentStorageCommon,
+            ComponentID::Bolt => &mut self.bolt as &mut dyn ComponentStorageCommon,
+            ComponentID::Mana => &mut self.mana as &mut dyn ComponentStorageCommon,
+            ComponentID::Team => &mut self.team as &mut dyn ComponentStorageCommon,
+            ComponentID::Tower => &mut self.tower as &mut dyn ComponentStorageCommon,
+            ComponentID::Experience => &mut self.experience as &mut dyn ComponentStorageCommon,
+            ComponentID::Gold => &mut self.gold as &mut dyn ComponentStorageCommon,
+            ComponentID::Inventory => &mut self.inventory as &mut dyn ComponentStorageCommon,
         }
     }
 
@@ -644,7 +789,17 @@ impl World {
             ComponentID::Projectile => insert(&mut self.projectile, id, cid, data),
             ComponentID::CasterMinion => insert(&mut self.caster_minion, id, cid, data),
             ComponentID::Status => insert(&mut self.status, id, cid, data),
-            ComponentID::Collision => insert(&mut self.status, id, cid, data),
+            ComponentID::Collision => insert(&mut self.collision, id, cid, data),
+            ComponentID::Death => insert(&mut self.death, id, cid, data),
+            ComponentID::Modifiers => insert(&mut self.modifiers, id, cid, data),
+            ComponentID::Aoe => insert(&mut self.aoe, id, cid, data),
+            ComponentID::Bolt => insert(&mut self.bolt, id, cid, data),
+            ComponentID::Mana => insert(&mut self.mana, id, cid, data),
+            ComponentID::Team => insert(&mut self.team, id, cid, data),
+            ComponentID::Tower => insert(&mut self.tower, id, cid, data),
+            ComponentID::Experience => insert(&mut self.experience, id, cid, data),
+            ComponentID::Gold => insert(&mut self.gold, id, cid, data),
+            ComponentID::Inventory => insert(&mut self.inventory, id, cid, data),
             // _ => panic!("Deserialization not implemented for component id: {}", cid)
         }
     }
@@ -704,72 +859,64 @@ impl World {
         ComponentID::iter().filter(|cid| self.has_component(id, cid)).collect()
     }
 
-    pub fn diff(&self, other: &Self) -> Vec<String> {
-        let mut diff = vec![];
-        diff.extend(
-            other.characters.difference(&self.characters)
-            .map(|cid| format!("Self missing cid {:?}", *cid))
-        );
-        diff.extend(
-            self.characters.difference(&other.characters)
-            .map(|cid| format!("Other missing cid {:?}", *cid))
-        );
-        for cid in self.characters.intersection(&other.characters) {
-            let self_comp = self.get_components(cid);
-            let other_comp = other.get_components(cid);
-            diff.extend(
-                other_comp.difference(&self_comp)
-                .map(|comp_id| format!("Self {:?} missing component {:?}", *cid, *comp_id))
-            );
-            diff.extend(
-                self_comp.difference(&other_comp)
-                .map(|comp_id| format!("Other {:?} missing component {:?}", *cid, *comp_id))
-            );
-            diff.extend(self_comp.intersection(&other_comp).filter_map(|comp_id| {
-                match comp_id {
-                    ComponentID::Base => {
-                        let mut diff = vec![];
-                        let s = self.base.get_component(cid).ok()?;
-                        let o = other.base.get_component(cid).ok()?;
-                        if s.ctype != o.ctype {
-                            diff.push(format!("{:?}.{:?}: Self ctype: {:?}, Other ctype: {:?}", comp_id, cid, s.ctype, o.ctype));
-                        }
-                        let pos_diff = (s.position - o.position).magnitude();
-                        if pos_diff > 0.0 {
-                            diff.push(format!("{:?}.{:?}: Self pos - other pos = {}", comp_id, cid, pos_diff));
-                        }
-                        let center_offset_diff = (s.center_offset - o.center_offset).magnitude();
-                        if center_offset_diff > 0.0 {
-                            diff.push(format!("{:?}.{:?}: Self center_offset - other center_offset = {}", comp_id, cid, center_offset_diff));
-                        }
-                        let speed_diff = s.speed - o.speed;
-                        if speed_diff > 0.0 {
-                            diff.push(format!("{:?}.{:?}: Self speed - other speed = {}", comp_id, cid, speed_diff));
-                        }
-                        let ad_diff = s.attack_damage - o.attack_damage;
-                        if ad_diff > 0.0 {
-                            diff.push(format!("{:?}.{:?}: Self ad - other ad = {}", comp_id, cid, ad_diff));
-                        }
-                        let range_diff = s.range - o.range;
-                        if range_diff > 0.0 {
-                            diff.push(format!("{:?}.{:?}: Self range - other range = {}", comp_id, cid, range_diff));
-                        }
-                        let as_diff = s.attack_speed - o.attack_speed;
-                        if as_diff > 0.0 {
-                            diff.push(format!("{:?}.{:?}: Self as - other as = {}", comp_id, cid, as_diff));
-                        }
-                        if s.flip != o.flip {
-                            diff.push(format!("{:?}.{:?}: Self flip {:?}, other flip {:?}", comp_id, cid, s.flip, o.flip));
-                        }
-                        if s.targetable != o.targetable {
-                            diff.push(format!("{:?}.{:?}: Self targetable {:?}, other targetable {:?}", comp_id, cid, s.targetable, o.targetable));
-                        }
+    // deterministic hash of the tick, the character set and every component storage
+    // client and server worlds at the same tick should always produce the same hash
+    pub fn state_hash(&self) -> u64 {
+        self.hash_characters(&self.characters)
+    }
 
-                        Some(diff.into_iter())
+    // whether a character is in both worlds with exactly the same components
+    pub fn same_character(&self, other: &World, id: &CharacterID) -> bool {
+        self.characters.contains(id) && other.characters.contains(id)
+            && ComponentID::iter().all(|cid| self.serialize_component(id, &cid) == other.serialize_component(id, &cid))
+    }
+
+    fn hash_characters(&self, characters: &HashSet<CharacterID>) -> u64 {
+        let mut hasher = StateHasher::new();
+        hasher.write(&self.tick.to_le_bytes());
+        for cid in characters.iter().sorted() {
+            hasher.write_num(cid.get_num());
+        }
+        for (index, comp_id) in ComponentID::iter().enumerate() {
+            // separate storages so that moving data between components changes the hash
+            hasher.write_num(index as u64);
+            self.get_storage(&comp_id).state_hash(&mut hasher, characters);
+        }
+        hasher.finish()
+    }
+
+    // structural diff against another world, listing added/removed characters and every field
+    // that differs between components
+    pub fn diff(&self, other: &Self) -> WorldDiff {
+        let mut diff = WorldDiff {
+            added: other.characters.difference(&self.characters).copied().sorted().collect(),
+            removed: self.characters.difference(&other.characters).copied().sorted().collect(),
+            components: HashMap::new(),
+        };
+        for comp_id in ComponentID::iter() {
+            let (this, that) = (self.get_storage(&comp_id), other.get_storage(&comp_id));
+            let changes = this.get_characters().into_iter()
+                .chain(that.get_characters())
+                .sorted()
+                .dedup()
+                .filter_map(|cid| match (this.to_json(&cid), that.to_json(&cid)) {
+                    (Some(this_value), Some(other_value)) => {
+                        let mut fields = vec![];
+                        diff::diff_values("", &this_value, &other_value, &mut fields);
+                        if fields.is_empty() {
+                            None
+                        } else {
+                            Some(ComponentDiff::Changed(cid, fields))
+                        }
                     },
-                    _ => None,
-                }
-            }).flatten());
+                    (None, Some(_)) => Some(ComponentDiff::Added(cid)),
+                    (Some(_), None) => Some(ComponentDiff::Removed(cid)),
+                    (None, None) => None,
+                })
+                .collect_vec();
+            if !changes.is_empty() {
+                diff.components.insert(comp_id, changes);
+            }
         }
         diff
     }
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use nalgebra::Vector2;
+
+use super::{component::ComponentID, system::{health::CharacterHealth, movement::Movement}, commands::{FixWorld, UpdateCharacter}};
+
+// serialized components of one character, keyed by component
+pub type ComponentData = HashMap<ComponentID, Vec<u8>>;
+
+// the first byte of a packed component is a set of flags saying which fields are left out
+// all packing is lossless, since the client has to end up with exactly the server's state
+const HEALTH_SAME: u8 = 1;
+const HEALTH_FULL: u8 = 2;
+const MAX_HEALTH_SAME: u8 = 4;
+
+const DESTINATION_NONE: u8 = 1;
+const DESTINATION_X_SAME: u8 = 2;
+const DESTINATION_Y_SAME: u8 = 4;
+const PATH_SAME: u8 = 8;
+
+struct PackReader<'a> {
+    data: &'a [u8],
+    flags: u8,
+}
+
+impl<'a> PackReader<'a> {
+    fn new(data: &'a [u8]) -> Result<Self, bincode::Error> {
+        match data.split_first() {
+            Some((flags, data)) => Ok(Self { data, flags: *flags }),
+            None => Err(Box::new(bincode::ErrorKind::Custom("Empty packed component".to_string()))),
+        }
+    }
+
+    fn has(&self, flag: u8) -> bool {
+        self.flags & flag != 0
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], bincode::Error> {
+        if self.data.len() < len {
+            return Err(Box::new(bincode::ErrorKind::Custom("Packed component is too short".to_string())));
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn read_f32(&mut self) -> Result<f32, bincode::Error> {
+        let num = self.read_bytes(4)?;
+        Ok(f32::from_le_bytes([num[0], num[1], num[2], num[3]]))
+    }
+
+    fn read_u16(&mut self) -> Result<u16, bincode::Error> {
+        let num = self.read_bytes(2)?;
+        Ok(u16::from_le_bytes([num[0], num[1]]))
+    }
+}
+
+fn same(a: f32, b: f32) -> bool {
+    a.to_bits() == b.to_bits()
+}
+
+fn pack_health(current: &CharacterHealth, baseline: Option<&CharacterHealth>) -> Vec<u8> {
+    let mut flags = 0;
+    let mut data = vec![];
+    match baseline {
+        Some(baseline) if same(baseline.max_health, current.max_health) => flags |= MAX_HEALTH_SAME,
+        _ => data.extend(current.max_health.to_le_bytes()),
+    }
+    match baseline {
+        Some(baseline) if same(baseline.health, current.health) => flags |= HEALTH_SAME,
+        _ if same(current.health, current.max_health) => flags |= HEALTH_FULL,
+        _ => data.extend(current.health.to_le_bytes()),
+    }
+    [vec![flags], data].concat()
+}
+
+fn unpack_health(packed: &[u8], baseline: Option<&CharacterHealth>) -> Result<CharacterHealth, bincode::Error> {
+    let mut reader = PackReader::new(packed)?;
+    let missing = || Box::new(bincode::ErrorKind::Custom("Packed health is missing its baseline".to_string()));
+    let max_health = match (reader.has(MAX_HEALTH_SAME), baseline) {
+        (true, Some(baseline)) => baseline.max_health,
+        (true, None) => return Err(missing()),
+        (false, _) => reader.read_f32()?,
+    };
+    let health = match (reader.has(HEALTH_SAME), reader.has(HEALTH_FULL), baseline) {
+        (true, _, Some(baseline)) => baseline.health,
+        (true, _, None) => return Err(missing()),
+        (false, true, _) => max_health,
+        (false, false, _) => reader.read_f32()?,
+    };
+    Ok(CharacterHealth { health, max_health })
+}
+
+fn same_path(a: &[Vector2<f32>], b: &[Vector2<f32>]) -> bool {
+    a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| same(a.x, b.x) && same(a.y, b.y))
+}
+
+fn pack_movement(current: &Movement, baseline: Option<&Movement>) -> Vec<u8> {
+    let mut flags = 0;
+    let mut data = vec![];
+    match baseline {
+        Some(baseline) if same_path(&baseline.path, &current.path) => flags |= PATH_SAME,
+        _ => {
+            data.extend((current.path.len() as u16).to_le_bytes());
+            for waypoint in &current.path {
+                data.extend(waypoint.x.to_le_bytes());
+                data.extend(waypoint.y.to_le_bytes());
+            }
+        }
+    }
+    let baseline = baseline.and_then(|baseline| baseline.destination);
+    match current.destination {
+        None => flags |= DESTINATION_NONE,
+        Some(destination) => {
+            match baseline {
+                Some(baseline) if same(baseline.x, destination.x) => flags |= DESTINATION_X_SAME,
+                _ => data.extend(destination.x.to_le_bytes()),
+            }
+            match baseline {
+                Some(baseline) if same(baseline.y, destination.y) => flags |= DESTINATION_Y_SAME,
+                _ => data.extend(destination.y.to_le_bytes()),
+            }
+        }
+    }
+    [vec![flags], data].concat()
+}
+
+fn unpack_movement(packed: &[u8], baseline: Option<&Movement>) -> Result<Movement, bincode::Error> {
+    let mut reader = PackReader::new(packed)?;
+    let missing = || Box::new(bincode::ErrorKind::Custom("Packed movement is missing its baseline".to_string()));
+    let path = match (reader.has(PATH_SAME), baseline) {
+        (true, Some(baseline)) => baseline.path.clone(),
+        (true, None) => return Err(missing()),
+        (false, _) => {
+            let len = reader.read_u16()?;
+            let mut path = Vec::with_capacity(len as usize);
+            for _ in 0..len {
+                path.push(Vector2::new(reader.read_f32()?, reader.read_f32()?));
+            }
+            path
+        }
+    };
+    if reader.has(DESTINATION_NONE) {
+        return Ok(Movement { destination: None, path });
+    }
+    let baseline = baseline.and_then(|baseline| baseline.destination);
+    let x = match (reader.has(DESTINATION_X_SAME), baseline) {
+        (true, Some(baseline)) => baseline.x,
+        (true, None) => return Err(missing()),
+        (false, _) => reader.read_f32()?,
+    };
+    let y = match (reader.has(DESTINATION_Y_SAME), baseline) {
+        (true, Some(baseline)) => baseline.y,
+        (true, None) => return Err(missing()),
+        (false, _) => reader.read_f32()?,
+    };
+    Ok(Movement { destination: Some(Vector2::new(x, y)), path })
+}
+
+// pack a serialized component against the client's baseline of it
+// components without a packed form are sent as their usual bincode bytes
+pub fn pack_component(comp_id: &ComponentID, data: &[u8], baseline: Option<&[u8]>) -> Result<Vec<u8>, bincode::Error> {
+    Ok(match comp_id {
+        ComponentID::Health => pack_health(
+            &bincode::deserialize(data)?,
+            baseline.map(bincode::deserialize).transpose()?.as_ref()
+        ),
+        ComponentID::Movement => pack_movement(
+            &bincode::deserialize(data)?,
+            baseline.map(bincode::deserialize).transpose()?.as_ref()
+        ),
+        _ => data.to_vec(),
+    })
+}
+
+// the inverse of pack_component, giving back the usual bincode bytes
+pub fn unpack_component(comp_id: &ComponentID, packed: &[u8], baseline: Option<&[u8]>) -> Result<Vec<u8>, bincode::Error> {
+    match comp_id {
+        ComponentID::Health => bincode::serialize(&unpack_health(
+            packed,
+            baseline.map(bincode::deserialize).transpose()?.as_ref()
+        )?),
+        ComponentID::Movement => bincode::serialize(&unpack_movement(
+            packed,
+            baseline.map(bincode::deserialize).transpose()?.as_ref()
+        )?),
+        _ => Ok(packed.to_vec()),
+    }
+}
+
+// find the components that changed since the baseline and pack them
+// returns the changed components and the components that no longer exist
+pub fn make_delta(current: &ComponentData, baseline: Option<&ComponentData>) -> Result<(ComponentData, Vec<ComponentID>), bincode::Error> {
+    let mut changed = HashMap::new();
+    for (comp_id, data) in current {
+        let base_data = baseline.and_then(|baseline| baseline.get(comp_id));
+        if base_data != Some(data) {
+            changed.insert(*comp_id, pack_component(comp_id, data, base_data.map(|d| d.as_slice()))?);
+        }
+    }
+    let mut removed: Vec<ComponentID> = baseline
+        .map(|baseline| baseline.keys().filter(|comp_id| !current.contains_key(comp_id)).copied().collect())
+        .unwrap_or_default();
+    removed.sort_by_key(|comp_id| *comp_id as u32);
+    Ok((changed, removed))
+}
+
+// rebuild the full state of the character from the FixWorld and the baseline it was made against
+pub fn resolve_delta(fix: &FixWorld, baseline: Option<&ComponentData>) -> Result<UpdateCharacter, bincode::Error> {
+    let mut components = baseline.cloned().unwrap_or_default();
+    for comp_id in &fix.removed {
+        components.remove(comp_id);
+    }
+    for (comp_id, packed) in &fix.changed {
+        let base_data = baseline.and_then(|baseline| baseline.get(comp_id));
+        let data = unpack_component(comp_id, packed, base_data.map(|d| d.as_slice()))?;
+        components.insert(*comp_id, data);
+    }
+    Ok(UpdateCharacter {
+        id: fix.id,
+        components,
+    })
+}
@@ -1,7 +1,7 @@
 use std::net::SocketAddr;
 
 use crate::{model::{player::{server::PlayerCommand, model::{PlayerID, PlayerDataView}, commands::ChatMessage}, Subscription, PrintError}, server::{commands::{ProtocolSpec, SendCommands}, main::Server}, networking::Protocol};
-use super::{commands::{UpdateCharacter, GenerateCharacter, ListChar, EnsureCharacter, ClearWorld, WorldCommand, GlobalCommand}, character::CharacterType, World, system::collision::CollisionInfo};
+use super::{commands::{UpdateCharacter, GenerateCharacter, ListChar, EnsureCharacter, ClearWorld, WorldCommand, GlobalCommand, FixWorldAck, FixWorldNack}, character::CharacterType, system::death::team_spawn};
 
 impl<'a> PlayerCommand<'a> for UpdateCharacter {
     const PROTOCOL: ProtocolSpec = ProtocolSpec::Both;
@@ -17,8 +17,13 @@ impl<'a> PlayerCommand<'a> for GenerateCharacter {
     const PROTOCOL: ProtocolSpec = ProtocolSpec::Both;
 
     fn run(self, tcp_addr: &std::net::SocketAddr, id: &PlayerID, server: &mut Server) {
+        if !server.matches.in_progress() {
+            return server.connection.send(Protocol::TCP, tcp_addr, &ChatMessage("Characters can only be spawned during a match".to_string())).print();
+        }
         let cid = server.character_id_gen.generate();
-        let command = WorldCommand::World(GlobalCommand::CreateCharacter(cid, self.0));
+        // characters join the team of the player that made them
+        let team = server.player_manager.get_player(id).and_then(|player| player.team);
+        let command = WorldCommand::World(GlobalCommand::CreateCharacter(cid, self.0, team, team_spawn(&server.world, team)));
         server.run_world_command(Some(tcp_addr), command);
         // let id = match server.world.create_character(&mut server.character_id_gen, self.0) {
         //     Ok(id) => {
@@ -72,6 +77,7 @@ impl<'a> PlayerCommand<'a> for ListChar {
 impl<'a> PlayerCommand<'a> for EnsureCharacter {
     const PROTOCOL: ProtocolSpec = ProtocolSpec::Both;
 
+    // spawning is limited to matches by GenerateCharacter
     fn run(self, addr: &SocketAddr, pid: &PlayerID, server: &mut Server) {
         match server.player_manager.get_player(pid) {
             Some(player) => {
@@ -90,9 +96,27 @@ impl<'a> PlayerCommand<'a> for EnsureCharacter {
 impl<'a> PlayerCommand<'a> for ClearWorld {
     const PROTOCOL: ProtocolSpec = ProtocolSpec::One(Protocol::TCP);
 
-    fn run(self, _: &SocketAddr, _: &PlayerID, server: &mut Server) {
+    fn run(self, addr: &SocketAddr, _: &PlayerID, server: &mut Server) {
+        if server.matches.in_progress() {
+            return server.connection.send(Protocol::TCP, addr, &ChatMessage("Can't clear the world during a match".to_string())).print();
+        }
         server.broadcast(Subscription::World, Protocol::TCP, &self);
-        
-        server.world = World::from(&server.world_template);
+        server.reset_world();
+    }
+}
+
+impl<'a> PlayerCommand<'a> for FixWorldAck {
+    const PROTOCOL: ProtocolSpec = ProtocolSpec::One(Protocol::UDP);
+
+    fn run(self, _: &SocketAddr, pid: &PlayerID, server: &mut Server) {
+        server.update_loop.ack(pid, self.id, self.tick);
+    }
+}
+
+impl<'a> PlayerCommand<'a> for FixWorldNack {
+    const PROTOCOL: ProtocolSpec = ProtocolSpec::One(Protocol::UDP);
+
+    fn run(self, _: &SocketAddr, pid: &PlayerID, server: &mut Server) {
+        server.update_loop.nack(pid, self.id);
     }
 }
@@ -3,7 +3,7 @@ use serde::{Serialize, Deserialize};
 
 use crate::model::{WorldTick, world::{character::CharacterID, CharacterCommandState, WorldError, World, component::{ComponentStorageContainer, ComponentUpdate, ComponentUpdateData}, Update, WorldErrorI}, TICK_RATE};
 
-use super::{auto_attack::fsm::{Fsm, self}, status::{StatusID, StatusPrio, Status, StatusUpdate}};
+use super::{auto_attack::fsm::{Fsm, self}, status::{StatusID, StatusPrio, Status, StatusUpdate, is_silenced}, mana::{has_mana, can_afford_fire, make_mana_cost_update}};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Execution<E> {
@@ -49,16 +49,31 @@ impl<'a, E> Ability<E> where E: Serialize + Deserialize<'a> + Clone {
         Some(AbilityUpdate(a))
     }
 
-    pub fn validate_command(&self, world: &World, cid: &CharacterID, cmd: &AbilityCommand<E>) -> Result<CharacterCommandState, WorldError> {
+    pub fn validate_command(&self, world: &World, info: &AbilityInfo, cid: &CharacterID, cmd: &AbilityCommand<E>) -> Result<CharacterCommandState, WorldError> {
         if cmd.duration < 0.0 || cmd.cooldown < 0.0 {
             return Err(WorldErrorI::InvalidCommandData(*cid, "Ability cannot have negative cooldown or duration".to_string()).err());
         }
+        if is_silenced(world, cid) {
+            return Ok(CharacterCommandState::Queued);
+        }
+        if !has_mana(world, cid, info.cost) {
+            return Err(WorldErrorI::InsufficientMana(*cid).err());
+        }
         if self.get_override_status().can_override(&world.status.get_component(cid)?.current) {
             return Ok(CharacterCommandState::Ready);
         }
         Ok(CharacterCommandState::Queued)
     }
 
+    // whether the cast's fire event happens during this update
+    pub fn fires(&self, world: &World, info: &AbilityInfo, delta_time: f32) -> bool {
+        self.execution.as_ref().is_some_and(|execution| {
+            let timer = (world.tick - execution.time_start) as f32 * 1.0 / TICK_RATE;
+            info.fsm.get_state_changes(execution.duration, timer, timer + delta_time).0.iter()
+                .any(|change| matches!(change, fsm::Changes::Event(_, FireEvent)))
+        })
+    }
+
     pub fn update<T>(&self, world: &World, info: &AbilityInfo, commands: &Vec<AbilityCommand<E>>, cid: &CharacterID, delta_time: f32, fire: T) -> Result<(Option<AbilityUpdate<E>>, Vec<Update>), WorldError>
             where T: Fn(&World, &CharacterID) -> Result<Vec<Update>, WorldError> {
         let status = &world.status.get_component(cid)?.current;
@@ -67,16 +82,25 @@ impl<'a, E> Ability<E> where E: Serialize + Deserialize<'a> + Clone {
             let executing;
             let (mut casting, mut fire_attack_updates) = (false, vec![]);
             let mut cooldown_updates = None;
+            let mut cancelled = false;
             if let Some(execution) = &self.execution {
                 let timer = (world.tick - execution.time_start) as f32 * 1.0 / TICK_RATE;
                 let (changes, _changed) = info.fsm.get_state_changes(
                         execution.duration,
                         timer,
                         timer + delta_time);
+                let affordable = can_afford_fire(world, cid, self.status_id, info.cost, delta_time)?;
                 changes.iter().fold(Ok(()), |status, change| {
                     match (status?, change) {
+                    // mana may have been spent since the cast started, ex: on another ability
+                    ((), fsm::Changes::Event(_time_since, FireEvent)) if !affordable => {
+                        cancelled = true;
+                        Ok(())
+                    },
                     ((), fsm::Changes::Event(_time_since, FireEvent)) => {
                         fire_attack_updates = fire(world, cid)?;
+                        // paid on fire, so casts that get cancelled before this are free
+                        fire_attack_updates.extend(make_mana_cost_update(world, cid, info.cost));
                         cooldown_updates = Some(
                             world.tick +
                             f32::ceil(
@@ -88,8 +112,8 @@ impl<'a, E> Ability<E> where E: Serialize + Deserialize<'a> + Clone {
                     _ => Ok(()),
                 }})?;
                 let state = info.fsm.get_current_state(execution.duration, timer + delta_time);
-                executing = state != Phase::Complete;
-                casting = state == Phase::Casting;
+                executing = state != Phase::Complete && !cancelled;
+                casting = state == Phase::Casting && !cancelled;
             } else {
                 executing = false;
             }
@@ -191,6 +215,7 @@ pub struct FireEvent;
 #[derive(Clone)]
 pub struct AbilityInfo {
     pub fsm: Fsm<Phase, FireEvent>,
+    pub cost: f32, // mana
 }
 
 impl AbilityInfo {
@@ -206,7 +231,8 @@ impl AbilityInfo {
                 (wind_down_time, Phase::WindDown)
             ],
             Phase::Complete,
-            &[(fire_time, FireEvent)]).ok_or_else(|| WorldErrorI::BadLogic.err())?
+            &[(fire_time, FireEvent)]).ok_or_else(|| WorldErrorI::BadLogic.err())?,
+            cost: 0.0,
         })
     }
 }
@@ -0,0 +1,303 @@
+use itertools::Itertools;
+use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+
+use crate::model::{world::{character::CharacterID, component::{Component, ComponentUpdateData, GetComponentID, ComponentID, ComponentStorageContainer, ComponentStorageCommon, ComponentUpdate}, WorldError, WorldSystem, WorldInfo, ComponentSystem, commands::{CharacterCommand, WorldCommand}, World, CharacterCommandState, Update, WorldErrorI}, commands::GetCommandID, WorldTick, TICK_RATE};
+use super::{ability::{Ability, AbilityInfo, AbilityCommand, AbilityUpdate}, status::{StatusID, CrowdControl, make_crowd_control_updates}, health::{DamageKind, make_damage_updates}, death::is_alive, team::are_allies};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AoeExecution {
+    pub target_pos: Vector2<f32>,
+}
+
+// an area waiting to go off
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Detonation {
+    pub position: Vector2<f32>,
+    pub tick: WorldTick,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Aoe {
+    pub ability: Ability<AoeExecution>,
+    pub detonations: Vec<Detonation>,
+}
+
+#[derive(Clone)]
+pub struct AoeInfo {
+    pub ability: AbilityInfo,
+    pub range: f32,
+    pub radius: f32,
+    pub delay: f32, // time between the fire event and the area going off
+    pub duration: f32,
+    pub cooldown: f32,
+    pub damage: f32,
+    pub kind: DamageKind,
+    pub effect: Option<(CrowdControl, f32)>, // crowd control and how long it lasts
+}
+
+impl Default for Aoe {
+    fn default() -> Self {
+        Self { ability: Ability::new(StatusID::Aoe), detonations: vec![] }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum AoeUpdate {
+    New,
+    Ability(Ability<AoeExecution>),
+    Schedule(Detonation),
+    Detonate(WorldTick), // remove detonations that went off at or before this tick
+}
+
+impl Component for Aoe {
+    fn update(&self, update: &ComponentUpdateData) -> Self {
+        let mut next = self.clone();
+        if let ComponentUpdateData::Aoe(update) = update {
+            match update {
+                AoeUpdate::New => next = Aoe::default(),
+                AoeUpdate::Ability(ability) => next.ability = ability.clone(),
+                AoeUpdate::Schedule(detonation) => next.detonations.push(detonation.clone()),
+                AoeUpdate::Detonate(tick) => next.detonations.retain(|detonation| detonation.tick > *tick),
+            }
+        }
+        next
+    }
+}
+
+impl GetComponentID for Aoe {
+    const ID: ComponentID = ComponentID::Aoe;
+}
+
+fn get_info<'a>(world: &'a World, cid: &CharacterID) -> Result<&'a AoeInfo, WorldError> {
+    let ctype = world.base.get_component(cid)?.ctype;
+    world.info.aoe.get(&ctype).ok_or_else(|| WorldErrorI::MissingCharacterInfoComponent(ctype, ComponentID::Aoe).err())
+}
+
+// damage and crowd control for every character in the area, except the caster and its allies
+pub fn aoe_hit_updates(world: &World, source: &CharacterID, position: &Vector2<f32>, info: &AoeInfo) -> Result<Vec<Update>, WorldError> {
+    let mut targets = world.health.get_characters();
+    targets.sort();
+    let mut updates = vec![];
+    for target in targets {
+        if target == *source || !is_alive(world, &target) || are_allies(world, source, &target) {
+            continue;
+        }
+        let base = world.base.get_component(&target)?;
+        if !base.targetable {
+            continue;
+        }
+        let radius = world.collision.get_component(&target)
+            .map(|collision| collision.collider.radius())
+            .unwrap_or(0.0);
+        if (Vector2::new(base.position.x, base.position.y) - position).magnitude() > info.radius + radius {
+            continue;
+        }
+        if info.damage > 0.0 {
+            updates.extend(make_damage_updates(world, source, &target, info.kind, info.damage)?);
+        }
+        if let Some((cc, duration)) = info.effect {
+            updates.extend(make_crowd_control_updates(world, source, &target, cc, duration));
+        }
+    }
+    Ok(updates)
+}
+
+pub fn fire_aoe(world: &World, cid: &CharacterID) -> Result<Vec<Update>, WorldError> {
+    let info = get_info(world, cid)?;
+    let base = world.base.get_component(cid)?;
+    let execution = world.aoe.get_component(cid)?.ability.execution.as_ref()
+        .ok_or_else(|| WorldErrorI::UnexpectedComponentState(
+                *cid,
+                ComponentID::Aoe,
+                "Component called fire while not executing".to_string()).err())?;
+    // the caster can be pushed while casting, so the target can end up past the range
+    let pos = Vector2::new(base.position.x, base.position.y);
+    let dir = execution.data.target_pos - pos;
+    let position = if dir.magnitude() > info.range {
+        pos + dir.normalize() * info.range
+    } else {
+        execution.data.target_pos
+    };
+    if info.delay <= 0.0 {
+        return aoe_hit_updates(world, cid, &position, info);
+    }
+    Ok(vec![Update::Comp(ComponentUpdate {
+        cid: *cid,
+        data: ComponentUpdateData::Aoe(AoeUpdate::Schedule(Detonation {
+            position,
+            tick: world.tick + f32::ceil(info.delay * TICK_RATE) as WorldTick,
+        })),
+    })])
+}
+
+pub struct AoeAbilitySystem;
+
+impl WorldSystem for AoeAbilitySystem {
+    fn init_world_info(&self) -> Result<WorldInfo, WorldError> {
+        Ok(WorldInfo::new())
+    }
+}
+
+impl ComponentSystem for AoeAbilitySystem {
+    fn get_component_id(&self) -> ComponentID {
+        ComponentID::Aoe
+    }
+
+    fn validate_character_command(&self, world: &World, cid: &CharacterID, cmd: &CharacterCommand) -> Result<CharacterCommandState, WorldError> {
+        match cmd {
+            CharacterCommand::Aoe(cmd) => {
+                if !cmd.target_pos.x.is_finite() || !cmd.target_pos.y.is_finite() {
+                    return Err(WorldErrorI::InvalidCommand.err());
+                }
+                let info = get_info(world, cid)?;
+                let base = world.base.get_component(cid)?;
+                if (cmd.target_pos - Vector2::new(base.position.x, base.position.y)).magnitude() > info.range {
+                    return Err(WorldErrorI::OutOfRange(*cid).err());
+                }
+                let aoe = world.aoe.get_component(cid)?;
+                aoe.ability.validate_command(world, &info.ability, cid, &cmd.to_ability_command(world, cid)?)
+            },
+            _ => Err(WorldErrorI::InvalidCommandMapping.err())
+        }
+    }
+
+    fn update_character(&self, world: &World, commands: &[WorldCommand], cid: &CharacterID, delta_time: f32) -> Result<Vec<Update>, WorldError> {
+        let info = get_info(world, cid)?;
+        let aoe = world.aoe.get_component(cid)?;
+        let commands = commands.iter().filter_map(|cmd| match cmd {
+            WorldCommand::CharacterComponent(
+                ccid,
+                ComponentID::Aoe,
+                CharacterCommand::Aoe(cmd)) => {
+                    if *ccid == *cid {
+                        cmd.to_ability_command(world, cid).ok()
+                    } else { None }
+                },
+            _ => None
+        }).collect_vec();
+        let (au, updates) = aoe.ability.update(world, &info.ability, &commands, cid, delta_time, fire_aoe)?;
+
+        // earlier casts that are due go off now
+        let due = aoe.detonations.iter().filter(|detonation| detonation.tick <= world.tick).collect_vec();
+        let mut detonation_updates = vec![];
+        for detonation in &due {
+            detonation_updates.extend(aoe_hit_updates(world, cid, &detonation.position, info)?);
+        }
+        let detonate = (!due.is_empty()).then_some(Update::Comp(ComponentUpdate {
+            cid: *cid,
+            data: ComponentUpdateData::Aoe(AoeUpdate::Detonate(world.tick)),
+        }));
+        Ok(updates
+           .into_iter()
+           .chain(detonation_updates)
+           .chain(detonate)
+           .chain(
+               au.into_iter()
+               .map(|update| Update::Comp(
+                    ComponentUpdate {
+                        cid: *cid,
+                        data: ComponentUpdateData::Aoe(AoeUpdate::Ability(update.0))
+                    }
+               )))
+           .collect_vec())
+    }
+
+    fn reduce_changes(&self, cid: &CharacterID, world: &World, changes: &[ComponentUpdateData]) -> Result<Vec<ComponentUpdateData>, WorldError> {
+        use ComponentUpdateData::Aoe as CAoe;
+        if !world.characters.contains(cid) || world.aoe.get_component(cid).is_err() {
+            return changes.iter()
+                .filter(|change| matches!(change, CAoe(AoeUpdate::New)))
+                .cloned()
+                .at_most_one()
+                .map_err(|_| WorldErrorI::MultipleUpdateOverrides(*cid, ComponentID::Aoe).err())
+                .map(|change| change.into_iter().collect_vec());
+        }
+        let aoe = world.aoe.get_component(cid)?;
+        let ability = aoe.ability.reduce(cid, world, &changes.iter()
+            .filter_map(|change| match change {
+                CAoe(AoeUpdate::Ability(ability)) => Some(AbilityUpdate(ability.clone())),
+                _ => None
+            })
+            .at_most_one()
+            .map_err(|_| WorldErrorI::MultipleUpdateOverrides(*cid, ComponentID::Aoe).err())?
+            .into_iter()
+            .collect_vec())?;
+        let detonate = changes.iter()
+            .filter_map(|change| match change {
+                CAoe(AoeUpdate::Detonate(tick)) => Some(*tick),
+                _ => None,
+            })
+            .max();
+        // a character only schedules its own casts, so there is at most one per tick
+        let schedule = changes.iter()
+            .filter(|change| matches!(change, CAoe(AoeUpdate::Schedule(_))))
+            .cloned();
+        Ok(ability.map(|ability| CAoe(AoeUpdate::Ability(ability.0))).into_iter()
+            .chain(detonate.map(|tick| CAoe(AoeUpdate::Detonate(tick))))
+            .chain(schedule)
+            .collect_vec())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AoeCommand {
+    pub target_pos: Vector2<f32>,
+}
+
+impl AoeCommand {
+    pub fn to_ability_command(&self, world: &World, cid: &CharacterID) -> Result<AbilityCommand<AoeExecution>, WorldError> {
+        let info = get_info(world, cid)?;
+        Ok(AbilityCommand {
+            duration: info.duration,
+            cooldown: info.cooldown,
+            exec_data: AoeExecution { target_pos: self.target_pos }
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AoeRequest {
+    pub user: CharacterID,
+    pub target_pos: Vector2<f32>,
+}
+
+impl GetCommandID for AoeRequest {
+    fn command_id(&self) -> crate::model::commands::CommandID {
+        crate::model::commands::CommandID::AoeRequest
+    }
+}
+
+#[cfg(feature = "server")]
+pub mod server {
+    use std::net::SocketAddr;
+
+    use crate::{model::{player::{server::PlayerCommand, model::{PlayerID, PlayerDataView}, commands::ChatMessage}, PrintError, world::{component::ComponentID, commands::{WorldCommand, CharacterCommand}}}, server::{commands::{ProtocolSpec, SendCommands}, main::Server}, networking::Protocol};
+
+    use super::{AoeRequest, AoeCommand};
+
+    impl<'a> PlayerCommand<'a> for AoeRequest {
+        const PROTOCOL: ProtocolSpec = ProtocolSpec::One(Protocol::UDP);
+        fn run(self, addr: &SocketAddr, player_id: &PlayerID, server: &mut Server) {
+            // check if the player can use the requested character
+            if server.player_manager.can_use_character(player_id, &self.user) {
+                server.run_world_command(
+                    Some(addr),
+                    WorldCommand::CharacterComponent(
+                        self.user,
+                        ComponentID::Aoe,
+                        CharacterCommand::Aoe(AoeCommand {
+                            target_pos: self.target_pos,
+                        })
+                    )
+                );
+            } else {
+                server.connection.send(
+                    Protocol::TCP,
+                    addr,
+                    &ChatMessage("Error: no permission".to_string())
+                ).print()
+            }
+        }
+    }
+}
@@ -4,7 +4,7 @@ use serde::{Serialize, Deserialize};
 use crate::model::{world::{World, character::{CharacterID, CharacterType, CharacterIDRange}, commands::{CharacterCommand, WorldCommand}, WorldError, component::{ComponentID, GetComponentID, ComponentStorageContainer, ComponentUpdateData, Component, ComponentUpdate}, WorldInfo, WorldSystem, ComponentSystem, Update, CharacterCommandState, WorldErrorI}, commands::GetCommandID, WorldTick, TICK_RATE};
 use self::fsm::Fsm;
 
-use super::{movement::walk_to, projectile::{self, ProjectileCreationInfo}, base::{CharacterFlip, make_flip_update}, status::{StatusID, StatusPrio, StatusUpdate, Status}};
+use super::{movement::path_to, projectile::{self, ProjectileCreationInfo, ProjectileTarget}, base::{CharacterFlip, make_flip_update}, status::{StatusID, StatusPrio, StatusUpdate, Status}, death::is_alive, health::DamageKind, modifiers::{effective_stat, Stat}, team::are_allies};
 
 pub mod fsm;
 
@@ -158,9 +158,15 @@ impl ComponentSystem for AutoAttackSystem {
                 if !world.characters.contains(&cmd.target) {
                     return Err(WorldErrorI::MissingCharacter(cmd.target, "Cannot attack nonexistent character".to_string()).err())
                 }
+                if !is_alive(world, &cmd.target) {
+                    return Err(WorldErrorI::InvalidCommand.err());
+                }
                 if !world.base.get_component(&cmd.target)?.targetable {
                     return Err(WorldErrorI::InvalidCommand.err());
                 }
+                if are_allies(world, cid, &cmd.target) {
+                    return Err(WorldErrorI::AllyTarget(cmd.target).err());
+                }
                 let status = &world.status.get_component(cid)?.current;
                 if !OVERRIDE_STATUS.can_override(status) {
                     return Ok(CharacterCommandState::Queued)
@@ -248,10 +254,11 @@ impl ComponentSystem for AutoAttackSystem {
     fn update_character(&self, world: &World, commands: &[WorldCommand], cid: &CharacterID, delta_time: f32) -> Result<Vec<Update>, WorldError> {
         let status = &world.status.get_component(cid)?.current;
         let is_status = status.id == StatusID::AutoAttack;
-        let (ctype, attack_speed, range) = {
-            let base = world.base.get_component(cid)?;
-            (base.ctype, base.attack_speed, base.range)
-        };
+        let (ctype, attack_speed, range) = (
+            world.base.get_component(cid)?.ctype,
+            effective_stat(world, cid, Stat::AttackSpeed)?,
+            effective_stat(world, cid, Stat::Range)?,
+        );
         let attack_info = world.info.auto_attack.get(&ctype)
             .ok_or_else(|| WorldErrorI::MissingCharacterInfoComponent(ctype, ComponentID::AutoAttack).err())?.clone();
         let auto_attack = world.auto_attack.get_component(cid)?;
@@ -260,7 +267,7 @@ impl ComponentSystem for AutoAttackSystem {
             let (mut casting, mut fire_attack_updates) = (false, vec![]);
             let mut cooldown_updates = None;
             if let Some(execution) = &auto_attack.execution {
-                if world.characters.contains(&execution.target) {
+                if is_alive(world, &execution.target) {
                     let timer = (world.tick - execution.time_start) as f32 * 1.0 / TICK_RATE;
                     let (changes, _changed) = attack_info.fsm.get_state_changes(
                             attack_speed,
@@ -291,7 +298,7 @@ impl ComponentSystem for AutoAttackSystem {
 
             (executing, casting, fire_attack_updates, cooldown_updates)
         };
-        let targeting = auto_attack.targeting.as_ref().map_or(false, |targeting| world.characters.contains(&targeting.target));
+        let targeting = auto_attack.targeting.as_ref().map_or(false, |targeting| is_alive(world, &targeting.target));
         let on_cooldown = auto_attack.cooldown_timeout > world.tick;
         let command = commands.iter()
             .filter_map(|cmd| match cmd.clone() {
@@ -312,7 +319,7 @@ impl ComponentSystem for AutoAttackSystem {
             let AutoAttackTargeting { target, ids: _ } = auto_attack.targeting.as_ref().ok_or_else(|| WorldErrorI::BadLogic.err())?;
             let target_pos = world.base.get_component(target)?.position;
             let target_pos = Vector2::new(target_pos.x, target_pos.y);
-            walk_to(world, cid, &target_pos, range, delta_time)?
+            path_to(world, cid, &target_pos, range, delta_time)?
         } else { (false, vec![]) };
 
         // execution
@@ -577,10 +584,10 @@ fn auto_attack_fire(world: &World, cid: &CharacterID) -> Result<Vec<Update>, Wor
     // world.base.get_component_mut(cid)?.position.y -= 1.0;
     // println!("Fire action this long ago: {}, for now we just jump lol", time_since_fire);
     let mut changes = vec![];
-    let (ctype, damage) = {
-        let base = world.base.get_component(cid)?;
-        (base.ctype, base.attack_damage)
-    };
+    let (ctype, damage) = (
+        world.base.get_component(cid)?.ctype,
+        effective_stat(world, cid, Stat::AttackDamage)?,
+    );
     let execution = world.auto_attack.get_component(cid)?.execution.as_ref()
         .ok_or_else(|| WorldErrorI::UnexpectedComponentState(
             *cid,
@@ -609,9 +616,10 @@ fn auto_attack_fire(world: &World, cid: &CharacterID) -> Result<Vec<Update>, Wor
         starting_offset: aa_info.projectile_offset,
         speed: aa_info.projectile_speed,
         damage,
+        kind: DamageKind::Physical,
         proj_id: gen_id,
         origin,
-        target,
+        target: ProjectileTarget::Character(target),
     };
     changes.extend(projectile::create(world, &info)?.into_iter());
     Ok(changes)
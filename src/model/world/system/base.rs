@@ -3,6 +3,8 @@ use serde::{Serialize, Deserialize};
 
 use crate::model::world::{character::{CharacterType, CharacterID}, component::{GetComponentID, ComponentID, ComponentUpdateData, Component, ComponentUpdate}, WorldSystem, WorldInfo, WorldError, ComponentSystem, World, commands::{CharacterCommand, WorldCommand, Priority}, Update, CharacterCommandState, WorldErrorI};
 
+use super::collision::resolve_terrain;
+
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum CharacterFlip {
     Left, Right
@@ -46,6 +48,8 @@ pub struct CharacterBase {
     pub attack_damage: f32,
     pub range: f32,
     pub attack_speed: f32,
+    pub armor: f32,
+    pub magic_resist: f32,
     pub flip: CharacterFlip,
     pub targetable: bool,
 }
@@ -79,6 +83,8 @@ impl Default for CharacterBase {
             attack_damage: 0.0,
             range: 0.0,
             attack_speed: 0.0,
+            armor: 0.0,
+            magic_resist: 0.0,
             flip: CharacterFlip::Right,
             targetable: true,
         }
@@ -264,6 +270,13 @@ impl ComponentSystem for BaseSystem {
                 })
                .into_iter()
                .flat_map(|change| change.into_iter() // combine reduced updates
+                   .map(|(prio, sw)| match sw {
+                       // the combined movement is what gets checked against terrain
+                       CharacterBaseUpdateSwitch::PositionUpdate(CharacterBasePositionUpdate::Move(mov)) =>
+                           (prio, CharacterBaseUpdateSwitch::PositionUpdate(CharacterBasePositionUpdate::Move(
+                               resolve_terrain(world, cid, prio, &mov)))),
+                       _ => (prio, sw),
+                   })
                    .map(|change| ComponentUpdateData::Base(CharacterBaseUpdate::Update(change.0, change.1)))).collect(),
            |new| vec![ComponentUpdateData::Base(new)]) // if we do have a "New" update then
                                                        // only reduce to just that update
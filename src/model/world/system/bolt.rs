@@ -0,0 +1,241 @@
+use std::collections::BTreeSet;
+
+use itertools::Itertools;
+use nalgebra::{Vector2, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::model::{world::{character::CharacterID, component::{Component, ComponentUpdateData, GetComponentID, ComponentID, ComponentStorageContainer, ComponentUpdate}, WorldError, WorldSystem, WorldInfo, ComponentSystem, commands::{CharacterCommand, WorldCommand, Priority}, World, CharacterCommandState, Update, WorldErrorI}, commands::GetCommandID};
+use super::{ability::{Ability, AbilityInfo, AbilityCommand, AbilityUpdate}, status::StatusID, health::DamageKind, collision::Layer, base::{CharacterFlip, make_flip_update}, projectile::{self, ProjectileCreationInfo, ProjectileTarget, Skillshot}};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BoltExecution {
+    pub target_pos: Vector2<f32>,
+    pub projectile_id: CharacterID,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Bolt {
+    pub ability: Ability<BoltExecution>,
+}
+
+#[derive(Clone)]
+pub struct BoltInfo {
+    pub ability: AbilityInfo,
+    pub range: f32,
+    pub radius: f32,
+    pub speed: f32,
+    pub duration: f32,
+    pub cooldown: f32,
+    pub damage: f32,
+    pub kind: DamageKind,
+    pub projectile_offset: Vector3<f32>,
+    pub layers: BTreeSet<Layer>,
+}
+
+impl Default for Bolt {
+    fn default() -> Self {
+        Self { ability: Ability::new(StatusID::Bolt) }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum BoltUpdate {
+    New,
+    Ability(Ability<BoltExecution>),
+}
+
+impl Component for Bolt {
+    fn update(&self, update: &ComponentUpdateData) -> Self {
+        let mut next = self.clone();
+        if let ComponentUpdateData::Bolt(update) = update {
+            match update {
+                BoltUpdate::New => next = Bolt::default(),
+                BoltUpdate::Ability(ability) => next.ability = ability.clone(),
+            }
+        }
+        next
+    }
+}
+
+impl GetComponentID for Bolt {
+    const ID: ComponentID = ComponentID::Bolt;
+}
+
+fn get_info<'a>(world: &'a World, cid: &CharacterID) -> Result<&'a BoltInfo, WorldError> {
+    let ctype = world.base.get_component(cid)?.ctype;
+    world.info.bolt.get(&ctype).ok_or_else(|| WorldErrorI::MissingCharacterInfoComponent(ctype, ComponentID::Bolt).err())
+}
+
+// fire a skillshot towards the target position, which only sets the direction
+pub fn fire_bolt(world: &World, cid: &CharacterID) -> Result<Vec<Update>, WorldError> {
+    let info = get_info(world, cid)?;
+    let base = world.base.get_component(cid)?;
+    let execution = world.bolt.get_component(cid)?.ability.execution.as_ref()
+        .ok_or_else(|| WorldErrorI::UnexpectedComponentState(
+                *cid,
+                ComponentID::Bolt,
+                "Component called fire while not executing".to_string()).err())?;
+    // casting on itself fires the way it is facing
+    let direction = match execution.data.target_pos - Vector2::new(base.position.x, base.position.y) {
+        dir if dir.x == 0.0 && dir.y == 0.0 => Vector2::new(base.flip.dir(), 0.0),
+        dir => dir,
+    };
+    let flip = make_flip_update(*cid, Priority::Cast, CharacterFlip::from_dir(&direction).unwrap_or(base.flip));
+    Ok(projectile::create(world, &ProjectileCreationInfo {
+        proj_id: execution.data.projectile_id,
+        origin: *cid,
+        target: ProjectileTarget::Direction(Skillshot {
+            direction,
+            range: info.range,
+            radius: info.radius,
+            layers: info.layers.clone(),
+        }),
+        starting_offset: info.projectile_offset,
+        speed: info.speed,
+        damage: info.damage,
+        kind: info.kind,
+    })?.into_iter().chain(Some(flip)).collect())
+}
+
+pub struct BoltAbilitySystem;
+
+impl WorldSystem for BoltAbilitySystem {
+    fn init_world_info(&self) -> Result<WorldInfo, WorldError> {
+        Ok(WorldInfo::new())
+    }
+}
+
+impl ComponentSystem for BoltAbilitySystem {
+    fn get_component_id(&self) -> ComponentID {
+        ComponentID::Bolt
+    }
+
+    fn validate_character_command(&self, world: &World, cid: &CharacterID, cmd: &CharacterCommand) -> Result<CharacterCommandState, WorldError> {
+        match cmd {
+            CharacterCommand::Bolt(cmd) => {
+                if !cmd.target_pos.x.is_finite() || !cmd.target_pos.y.is_finite() {
+                    return Err(WorldErrorI::InvalidCommand.err());
+                }
+                let bolt = world.bolt.get_component(cid)?;
+                bolt.ability.validate_command(world, &get_info(world, cid)?.ability, cid, &cmd.to_ability_command(world, cid)?)
+            },
+            _ => Err(WorldErrorI::InvalidCommandMapping.err())
+        }
+    }
+
+    fn update_character(&self, world: &World, commands: &[WorldCommand], cid: &CharacterID, delta_time: f32) -> Result<Vec<Update>, WorldError> {
+        let info = get_info(world, cid)?;
+        let bolt = world.bolt.get_component(cid)?;
+        let commands = commands.iter().filter_map(|cmd| match cmd {
+            WorldCommand::CharacterComponent(
+                ccid,
+                ComponentID::Bolt,
+                CharacterCommand::Bolt(cmd)) => {
+                    if *ccid == *cid {
+                        cmd.to_ability_command(world, cid).ok()
+                    } else { None }
+                },
+            _ => None
+        }).collect_vec();
+        let (au, updates) = bolt.ability.update(world, &info.ability, &commands, cid, delta_time, fire_bolt)?;
+        Ok(updates
+           .into_iter()
+           .chain(
+               au.into_iter()
+               .map(|update| Update::Comp(
+                    ComponentUpdate {
+                        cid: *cid,
+                        data: ComponentUpdateData::Bolt(BoltUpdate::Ability(update.0))
+                    }
+               )))
+           .collect_vec())
+    }
+
+    fn reduce_changes(&self, cid: &CharacterID, world: &World, changes: &[ComponentUpdateData]) -> Result<Vec<ComponentUpdateData>, WorldError> {
+        use ComponentUpdateData::Bolt as CBolt;
+        if !world.characters.contains(cid) || world.bolt.get_component(cid).is_err() {
+            return changes.iter()
+                .filter(|change| matches!(change, CBolt(BoltUpdate::New)))
+                .cloned()
+                .at_most_one()
+                .map_err(|_| WorldErrorI::MultipleUpdateOverrides(*cid, ComponentID::Bolt).err())
+                .map(|change| change.into_iter().collect_vec());
+        }
+        let bolt = world.bolt.get_component(cid)?;
+        let ability = bolt.ability.reduce(cid, world, &changes.iter()
+            .filter_map(|change| match change {
+                CBolt(BoltUpdate::Ability(ability)) => Some(AbilityUpdate(ability.clone())),
+                _ => None
+            })
+            .at_most_one()
+            .map_err(|_| WorldErrorI::MultipleUpdateOverrides(*cid, ComponentID::Bolt).err())?
+            .into_iter()
+            .collect_vec())?;
+        Ok(ability.map(|ability| CBolt(BoltUpdate::Ability(ability.0))).into_iter().collect_vec())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BoltCommand {
+    pub target_pos: Vector2<f32>,
+    pub projectile_id: CharacterID,
+}
+
+impl BoltCommand {
+    pub fn to_ability_command(&self, world: &World, cid: &CharacterID) -> Result<AbilityCommand<BoltExecution>, WorldError> {
+        let info = get_info(world, cid)?;
+        Ok(AbilityCommand {
+            duration: info.duration,
+            cooldown: info.cooldown,
+            exec_data: BoltExecution { target_pos: self.target_pos, projectile_id: self.projectile_id }
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BoltRequest {
+    pub user: CharacterID,
+    pub target_pos: Vector2<f32>,
+}
+
+impl GetCommandID for BoltRequest {
+    fn command_id(&self) -> crate::model::commands::CommandID {
+        crate::model::commands::CommandID::BoltRequest
+    }
+}
+
+#[cfg(feature = "server")]
+pub mod server {
+    use std::net::SocketAddr;
+
+    use crate::{model::{player::{server::PlayerCommand, model::{PlayerID, PlayerDataView}, commands::ChatMessage}, PrintError, world::{component::ComponentID, commands::{WorldCommand, CharacterCommand}}}, server::{commands::{ProtocolSpec, SendCommands}, main::Server}, networking::Protocol};
+
+    use super::{BoltRequest, BoltCommand};
+
+    impl<'a> PlayerCommand<'a> for BoltRequest {
+        const PROTOCOL: ProtocolSpec = ProtocolSpec::One(Protocol::UDP);
+        fn run(self, addr: &SocketAddr, player_id: &PlayerID, server: &mut Server) {
+            // check if the player can use the requested character
+            if server.player_manager.can_use_character(player_id, &self.user) {
+                let projectile_id = server.character_id_gen.generate();
+                server.run_world_command(
+                    Some(addr),
+                    WorldCommand::CharacterComponent(
+                        self.user,
+                        ComponentID::Bolt,
+                        CharacterCommand::Bolt(BoltCommand {
+                            target_pos: self.target_pos,
+                            projectile_id,
+                        })
+                    )
+                );
+            } else {
+                server.connection.send(
+                    Protocol::TCP,
+                    addr,
+                    &ChatMessage("Error: no permission".to_string())
+                ).print()
+            }
+        }
+    }
+}
@@ -1,18 +1,22 @@
-use std::collections::HashSet;
+use std::{collections::{HashSet, BTreeSet}, rc::Rc, path::Path};
 
-use nalgebra::Vector2;
+use nalgebra::{Vector2, Vector3};
 use serde::{Serialize, Deserialize};
 
-use crate::model::world::{component::{GetComponentID, ComponentID, Component, ComponentUpdateData}, WorldSystem, WorldInfo, WorldError, ComponentSystem, World, character::CharacterID, commands::{CharacterCommand, WorldCommand}, CharacterCommandState, Update, WorldErrorI};
+use crate::model::world::{component::{GetComponentID, ComponentID, Component, ComponentUpdateData, ComponentStorageContainer, ComponentStorageCommon}, WorldSystem, WorldInfo, WorldError, ComponentSystem, World, character::CharacterID, commands::{CharacterCommand, WorldCommand, Priority}, CharacterCommandState, Update, WorldErrorI};
 use image::io::Reader as ImageReader;
 
-const COLLISION_TEST_TEXTURE_PATH: &str = "map/collision.png";
+use super::{base::make_move_update, pathfinding::NavGrid, ground::GroundGrid, death::is_alive};
 
-#[derive(Eq, PartialEq, Hash, Serialize, Deserialize, Debug, Clone)]
+// how fast overlapping characters are pushed apart, in world units per second
+const PUSH_SPEED: f32 = 2.0;
+
+#[derive(Eq, PartialEq, Hash, PartialOrd, Ord, Serialize, Deserialize, Debug, Clone, Copy)]
 pub enum Layer {
     Terrain,
     Minions,
     Players,
+    Structures,
 }
 
 #[derive(PartialEq, Serialize, Deserialize, Debug, Clone)]
@@ -22,10 +26,20 @@ pub enum Collider {
 }
 impl Eq for Collider {}
 
+impl Collider {
+    pub fn radius(&self) -> f32 {
+        match *self {
+            Collider::Point => 0.0,
+            Collider::Circle(radius) => radius,
+        }
+    }
+}
+
 #[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
 pub struct Collision {
     pub collider: Collider,
-    pub against: HashSet<Layer>
+    pub layer: Option<Layer>, // the layer other characters collide with this one on
+    pub against: BTreeSet<Layer> // ordered so that serialization is deterministic
 }
 
 impl GetComponentID for Collision {
@@ -34,7 +48,7 @@ impl GetComponentID for Collision {
 
 impl Default for Collision {
     fn default() -> Self {
-        Self { collider: Collider::Point, against: HashSet::new() }
+        Self { collider: Collider::Point, layer: None, against: BTreeSet::new() }
     }
 }
 
@@ -48,17 +62,24 @@ impl Component for Collision {
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
-pub struct CollisionUpdate(Collision);
+pub struct CollisionUpdate(pub Collision);
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct CollisionInfo {
-    pub terrain: HashSet<Vector2<i32>>
+    pub terrain: HashSet<Vector2<i32>>,
+    pub size: Vector2<i32>, // size of the collision image in pixels
+    pub world_size: f32, // the image covers this many world units, centered on the origin
+    pub ground: GroundGrid,
+    pub nav: NavGrid,
 }
 
 impl CollisionInfo {
-    pub fn test_collision() -> Self {
+    // opaque pixels of the image are terrain
+    pub fn load(path: &Path, world_size: f32, ground: GroundGrid) -> Result<Self, String> {
         let mut terrain = HashSet::new();
-        let img = ImageReader::open(COLLISION_TEST_TEXTURE_PATH).unwrap().decode().unwrap().to_rgba8();
+        let img = ImageReader::open(path).map_err(|err| err.to_string())?
+            .decode().map_err(|err| err.to_string())?
+            .to_rgba8();
         for j in 0..img.height() {
             for i in 0..img.width() {
                 if img[(i, j)].0[3] > 0 {
@@ -67,26 +88,159 @@ impl CollisionInfo {
             }
         }
 
-        Self { terrain }
+        let mut info = Self {
+            terrain,
+            size: Vector2::new(img.width() as i32, img.height() as i32),
+            world_size,
+            ground,
+            nav: NavGrid::default(),
+        };
+        info.nav = NavGrid::new(&info);
+        Ok(info)
+    }
+
+    fn pixel_size(&self) -> Vector2<f32> {
+        Vector2::new(self.world_size / self.size.x as f32, self.world_size / self.size.y as f32)
+    }
+
+    // the pixel containing a world position
+    pub fn to_pixel(&self, pos: &Vector2<f32>) -> Vector2<i32> {
+        let pixel_size = self.pixel_size();
+        Vector2::new(
+            ((pos.x + self.world_size / 2.0) / pixel_size.x).floor() as i32,
+            ((pos.y + self.world_size / 2.0) / pixel_size.y).floor() as i32,
+        )
+    }
+
+    pub fn collides(&self, collider: &Collider, pos: &Vector2<f32>) -> bool {
+        match *collider {
+            Collider::Point => self.terrain.contains(&self.to_pixel(pos)),
+            Collider::Circle(radius) => {
+                let pixel_size = self.pixel_size();
+                let (min, max) = (
+                    self.to_pixel(&(pos - Vector2::new(radius, radius))),
+                    self.to_pixel(&(pos + Vector2::new(radius, radius)))
+                );
+                (min.y..=max.y).any(|j| (min.x..=max.x).any(|i| {
+                    if !self.terrain.contains(&Vector2::new(i, j)) {
+                        return false;
+                    }
+                    // closest point of the pixel to the circle's center
+                    let corner = Vector2::new(
+                        i as f32 * pixel_size.x - self.world_size / 2.0,
+                        j as f32 * pixel_size.y - self.world_size / 2.0
+                    );
+                    let closest = Vector2::new(
+                        pos.x.clamp(corner.x, corner.x + pixel_size.x),
+                        pos.y.clamp(corner.y, corner.y + pixel_size.y)
+                    );
+                    (closest - pos).magnitude_squared() < radius * radius
+                }))
+            }
+        }
+    }
+
+    // whether a character can't stand at a position, because of terrain or impassable ground
+    pub fn blocks(&self, collider: &Collider, pos: &Vector2<f32>) -> bool {
+        !self.ground.passable(pos) || self.collides(collider, pos)
+    }
+
+    fn step_count(&self, mov: &Vector2<f32>) -> i32 {
+        // steps of at most half a pixel, so that thin walls can't be skipped
+        let step = f32::min(self.pixel_size().x, self.pixel_size().y) / 2.0;
+        i32::max(1, (mov.magnitude() / step).ceil() as i32)
+    }
+
+    // walk from a position, sliding along terrain that is in the way
+    // returns the movement that can actually be made
+    pub fn resolve_walk(&self, collider: &Collider, from: &Vector2<f32>, mov: &Vector2<f32>) -> Vector2<f32> {
+        if self.blocks(collider, from) {
+            // let characters that are already stuck walk out
+            return *mov;
+        }
+        let steps = self.step_count(mov);
+        let step = mov / steps as f32;
+        let mut pos = *from;
+        for _ in 0..steps {
+            let next = [pos + step, pos + Vector2::new(step.x, 0.0), pos + Vector2::new(0.0, step.y)]
+                .into_iter()
+                .find(|next| !self.blocks(collider, next));
+            match next {
+                Some(next) => pos = next,
+                None => break,
+            }
+        }
+        pos - from
+    }
+
+    // move in a straight line until hitting terrain, ex: skillshots
+    // returns the movement that can be made before the hit, or None if nothing is hit
+    pub fn cast(&self, collider: &Collider, from: &Vector2<f32>, mov: &Vector2<f32>) -> Option<Vector2<f32>> {
+        let steps = self.step_count(mov);
+        (1..=steps)
+            .find(|k| self.collides(collider, &(from + mov * (*k as f32 / steps as f32))))
+            .map(|k| mov * ((k - 1) as f32 / steps as f32))
+    }
+
+    // instantly move from a position, ex: flash. the character lands at the free position closest
+    // to the destination along the way there
+    pub fn resolve_blink(&self, collider: &Collider, from: &Vector2<f32>, mov: &Vector2<f32>) -> Vector2<f32> {
+        let steps = self.step_count(mov);
+        (0..=steps).rev()
+            .map(|k| mov * (k as f32 / steps as f32))
+            .find(|offset| !self.blocks(collider, &(from + offset)))
+            .unwrap_or_else(Vector2::zeros)
+    }
+}
+
+// resolve a character's movement for this tick against the terrain
+// walking slides along walls, while higher priority movement is treated as a blink
+pub fn resolve_terrain(world: &World, cid: &CharacterID, priority: Priority, mov: &Vector3<f32>) -> Vector3<f32> {
+    let (terrain, collision, base) = match (&world.info.terrain, world.collision.get_component(cid), world.base.get_component(cid)) {
+        (Some(terrain), Ok(collision), Ok(base)) if collision.against.contains(&Layer::Terrain) => (terrain, collision, base),
+        _ => return *mov,
+    };
+    let from = Vector2::new(base.position.x, base.position.y);
+    let mov2 = Vector2::new(mov.x, mov.y);
+    let resolved = match priority {
+        Priority::Walk => terrain.resolve_walk(&collision.collider, &from, &mov2),
+        _ => terrain.resolve_blink(&collision.collider, &from, &mov2),
+    };
+    Vector3::new(resolved.x, resolved.y, mov.z)
+}
+
+// the ground's movement multiplier under a character, for characters that collide with terrain
+// characters that end up on impassable ground anyway walk off it at full speed
+pub fn ground_speed(world: &World, cid: &CharacterID) -> f32 {
+    match (&world.info.terrain, world.collision.get_component(cid), world.base.get_component(cid)) {
+        (Some(terrain), Ok(collision), Ok(base)) if collision.against.contains(&Layer::Terrain) => {
+            match terrain.ground.speed(&Vector2::new(base.position.x, base.position.y)) {
+                speed if speed > 0.0 => speed,
+                _ => 1.0,
+            }
+        },
+        _ => 1.0,
     }
 }
 
 #[derive(Clone)]
 pub struct CollisionSystem {
-    info: CollisionInfo,
+    info: Rc<CollisionInfo>,
 }
 
 impl CollisionSystem {
     pub fn new(info: CollisionInfo) -> Self {
         Self {
-            info,
+            info: Rc::new(info),
         }
     }
 }
 
 impl WorldSystem for CollisionSystem {
     fn init_world_info(&self) -> Result<WorldInfo, WorldError> {
-        Ok(WorldInfo::new())
+        let mut info = WorldInfo::new();
+        info.terrain = Some(self.info.clone());
+        Ok(info)
     }
 }
 
@@ -95,15 +249,63 @@ impl ComponentSystem for CollisionSystem {
         ComponentID::Collision
     }
 
-    fn validate_character_command(&self, world: &World, cid: &CharacterID, cmd: &CharacterCommand) -> Result<CharacterCommandState, WorldError> {
+    fn validate_character_command(&self, _: &World, _: &CharacterID, _: &CharacterCommand) -> Result<CharacterCommandState, WorldError> {
         Err(WorldErrorI::InvalidCommandMapping.err())
     }
 
-    fn update_character(&self, world: &World, commands: &[WorldCommand], cid: &CharacterID, delta_time: f32) -> Result<Vec<Update>, WorldError> {
-        Ok(vec![])
+    // push this character out of the characters it overlaps with
+    // every character does this for itself, so each one moves half of the overlap
+    fn update_character(&self, world: &World, _: &[WorldCommand], cid: &CharacterID, delta_time: f32) -> Result<Vec<Update>, WorldError> {
+        // bodies don't push or get pushed
+        if !is_alive(world, cid) {
+            return Ok(vec![]);
+        }
+        let collision = world.collision.get_component(cid)?;
+        let base = world.base.get_component(cid)?;
+        let pos = Vector2::new(base.position.x, base.position.y);
+        let radius = collision.collider.radius();
+        let mut others = world.collision.get_characters();
+        others.sort();
+        let push = others.into_iter()
+            .filter(|other| *other != *cid && is_alive(world, other))
+            .filter_map(|other| {
+                let other_collision = world.collision.get_component(&other).ok()?;
+                if !other_collision.layer.map(|layer| collision.against.contains(&layer)).unwrap_or(false) {
+                    return None;
+                }
+                let other_base = world.base.get_component(&other).ok()?;
+                let min_dist = radius + other_collision.collider.radius();
+                let diff = pos - Vector2::new(other_base.position.x, other_base.position.y);
+                let dist = diff.magnitude();
+                if min_dist <= 0.0 || dist >= min_dist {
+                    return None;
+                }
+                let dir = if dist > 0.0 {
+                    diff / dist
+                } else if *cid < other {
+                    // exactly on top of each other, so split them apart by id
+                    Vector2::new(1.0, 0.0)
+                } else {
+                    Vector2::new(-1.0, 0.0)
+                };
+                Some(dir * (min_dist - dist) / 2.0)
+            })
+            .fold(Vector2::zeros(), |total, push| total + push);
+        let max_push = PUSH_SPEED * delta_time;
+        let push = if push.magnitude() > max_push { push.normalize() * max_push } else { push };
+        if push.x == 0.0 && push.y == 0.0 {
+            return Ok(vec![]);
+        }
+        Ok(vec![make_move_update(*cid, Priority::Walk, Vector3::new(push.x, push.y, 0.0))])
     }
 
-    fn reduce_changes(&self, cid: &CharacterID, world: &World, changes: &[ComponentUpdateData]) -> Result<Vec<ComponentUpdateData>, WorldError> {
-        Ok(vec![])
+    fn reduce_changes(&self, _: &CharacterID, _: &World, changes: &[ComponentUpdateData]) -> Result<Vec<ComponentUpdateData>, WorldError> {
+        // collision updates replace the whole component, so only the last one matters
+        Ok(changes.iter()
+            .rev()
+            .find(|change| matches!(change, ComponentUpdateData::Collision(_)))
+            .cloned()
+            .into_iter()
+            .collect())
     }
 }
@@ -0,0 +1,187 @@
+use nalgebra::{Vector2, Vector3};
+use serde::{Serialize, Deserialize};
+
+use crate::model::{WorldTick, player::model::TeamID, world::{World, character::CharacterID, component::{GetComponentID, ComponentID, ComponentUpdateData, Component, ComponentUpdate, ComponentStorageContainer}, WorldError, WorldInfo, WorldSystem, commands::{CharacterCommand, WorldCommand, Priority}, ComponentSystem, Update, WorldUpdate, WorldEvent, CharacterCommandState, WorldErrorI}};
+
+use super::{status::{StatusUpdate, Status, StatusPrio, StatusID}, health::CharacterHealthUpdate, base::{CharacterBaseUpdate, CharacterBaseUpdateSwitch, CharacterBasePositionUpdate}, movement::make_movement_component_update, modifiers::ModifiersUpdate, mana::ManaUpdate, experience::make_kill_experience_updates, gold::make_kill_gold_updates, team::get_team};
+
+// how long a character type stays dead
+// characters without a respawn time are removed once the cleanup time has passed, which leaves
+// time for their death animation
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct DeathInfo {
+    pub respawn_time: Option<f32>,
+    pub cleanup_time: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Dead {
+    pub killer: Option<CharacterID>,
+    pub timer: f32, // time since death
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Death {
+    pub last_hit: Option<CharacterID>, // who gets the kill if this character dies
+    pub dead: Option<Dead>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum DeathUpdate {
+    New,
+    Hit(CharacterID),
+    Die(Option<CharacterID>),
+    Timer(f32),
+    Respawn,
+}
+
+impl Component for Death {
+    fn update(&self, update: &ComponentUpdateData) -> Self {
+        let mut next = self.clone();
+        if let ComponentUpdateData::Death(update) = update {
+            match *update {
+                DeathUpdate::New | DeathUpdate::Respawn => next = Death::default(),
+                DeathUpdate::Hit(source) => next.last_hit = Some(source),
+                DeathUpdate::Die(killer) => next.dead = Some(Dead { killer, timer: 0.0 }),
+                DeathUpdate::Timer(delta_time) => if let Some(dead) = next.dead.as_mut() {
+                    dead.timer += delta_time;
+                },
+            }
+        }
+        next
+    }
+}
+
+impl GetComponentID for Death {
+    const ID: ComponentID = ComponentID::Death;
+}
+
+const DEAD_STATUS: Status = Status {
+    prio: StatusPrio::Dead,
+    id: StatusID::Dead,
+};
+
+// characters without a death component can't die
+pub fn is_alive(world: &World, cid: &CharacterID) -> bool {
+    world.characters.contains(cid) && world.death.get_component(cid)
+        .map(|death| death.dead.is_none())
+        .unwrap_or(true)
+}
+
+// record who hit a character, so that they get the kill
+pub fn make_hit_update(target: &CharacterID, source: &CharacterID) -> Update {
+    Update::Comp(ComponentUpdate {
+        cid: *target,
+        data: ComponentUpdateData::Death(DeathUpdate::Hit(*source)),
+    })
+}
+
+// the map's spawn point for a team's side, or the origin for characters without a team
+pub fn team_spawn(world: &World, team: Option<TeamID>) -> Vector2<f32> {
+    team.and_then(|team| world.info.spawns.get(team.index()))
+        .copied()
+        .unwrap_or_default()
+}
+
+// characters come back at their team's spawn point
+pub fn spawn_point(world: &World, cid: &CharacterID) -> Vector2<f32> {
+    team_spawn(world, get_team(world, cid))
+}
+
+pub struct DeathSystem;
+
+impl WorldSystem for DeathSystem {
+    fn init_world_info(&self) -> Result<WorldInfo, WorldError> {
+        Ok(WorldInfo::new())
+    }
+}
+
+impl ComponentSystem for DeathSystem {
+    fn get_component_id(&self) -> ComponentID {
+        ComponentID::Death
+    }
+
+    fn validate_character_command(&self, _: &World, _: &CharacterID, _: &CharacterCommand) -> Result<CharacterCommandState, WorldError> {
+        Err(WorldErrorI::InvalidCommandMapping.err())
+    }
+
+    // die once health runs out, then respawn or get removed when the timer is up
+    fn update_character(&self, world: &World, _: &[WorldCommand], cid: &CharacterID, delta_time: f32) -> Result<Vec<Update>, WorldError> {
+        let death = world.death.get_component(cid)?;
+        let health = world.health.get_component(cid)?;
+        let ctype = world.base.get_component(cid)?.ctype;
+        let info = world.info.death.get(&ctype)
+            .ok_or_else(|| WorldErrorI::MissingCharacterInfoComponent(ctype, ComponentID::Death).err())?;
+        let comp = |data| Update::Comp(ComponentUpdate { cid: *cid, data });
+        match death.dead {
+            None if health.health <= 0.0 => Ok(vec![
+                comp(ComponentUpdateData::Death(DeathUpdate::Die(death.last_hit))),
+                comp(ComponentUpdateData::Status(StatusUpdate::Try(DEAD_STATUS.prio, DEAD_STATUS))),
+                comp(ComponentUpdateData::Status(StatusUpdate::Cleanse)),
+                Update::Event(WorldEvent::Kill { target: *cid, killer: death.last_hit }),
+            ].into_iter()
+            .chain(make_kill_experience_updates(world, cid))
+            .chain(make_kill_gold_updates(world, cid, death.last_hit.as_ref()))
+            // the cleanse only removes status effects, timed modifiers like slows have to go too
+            // items stay, their modifiers never expire
+            .chain(world.modifiers.get_component(cid).ok()
+                .map(|_| comp(ComponentUpdateData::Modifiers(ModifiersUpdate::Expire(WorldTick::MAX)))))
+            // characters that can't move, ex: towers, shouldn't be given movement
+            .chain(world.movement.get_component(cid).ok()
+                .map(|_| make_movement_component_update(*cid, None, vec![])))
+            .collect()),
+            None => Ok(vec![]),
+            Some(dead) => match info.respawn_time {
+                Some(respawn_time) if dead.timer + delta_time >= respawn_time => {
+                    let spawn = spawn_point(world, cid);
+                    Ok(vec![
+                        comp(ComponentUpdateData::Death(DeathUpdate::Respawn)),
+                        comp(ComponentUpdateData::Status(StatusUpdate::Cancel(StatusID::Dead))),
+                        comp(ComponentUpdateData::Health(CharacterHealthUpdate::Change(health.max_health - health.health))),
+                        // teleports ignore terrain, unlike moves
+                        comp(ComponentUpdateData::Base(CharacterBaseUpdate::Update(
+                            Priority::Stun,
+                            CharacterBaseUpdateSwitch::PositionUpdate(CharacterBasePositionUpdate::Override(
+                                Vector3::new(spawn.x, spawn.y, 0.0)))))),
+                    ].into_iter()
+                    .chain(world.mana.get_component(cid).ok()
+                        .map(|mana| comp(ComponentUpdateData::Mana(ManaUpdate::Change(mana.max_mana - mana.mana)))))
+                    .collect())
+                },
+                None if dead.timer + delta_time >= info.cleanup_time => Ok(vec![
+                    Update::World(WorldUpdate::RemoveCharacterID(*cid)),
+                ]),
+                _ => Ok(vec![comp(ComponentUpdateData::Death(DeathUpdate::Timer(delta_time)))]),
+            },
+        }
+    }
+
+    fn reduce_changes(&self, cid: &CharacterID, world: &World, changes: &[ComponentUpdateData]) -> Result<Vec<ComponentUpdateData>, WorldError> {
+        use ComponentUpdateData::Death as CDeath;
+        if !world.characters.contains(cid) {
+            let new_changes: Vec<ComponentUpdateData> = changes.iter()
+                .filter(|new| matches!(*new, CDeath(DeathUpdate::New)))
+                .cloned().collect();
+            if new_changes.is_empty() {
+                return Err(WorldErrorI::InvalidReduceMapping(*cid, ComponentID::Death).err())
+            } else if new_changes.len() > 1 {
+                return Err(WorldErrorI::MultipleUpdateOverrides(*cid, ComponentID::Death).err())
+            } else {
+                return Ok(new_changes)
+            }
+        }
+        // the state change comes from this character's own update, so there is at most one
+        let state = changes.iter().find(|change| matches!(*change,
+            CDeath(DeathUpdate::Die(_)) | CDeath(DeathUpdate::Respawn) | CDeath(DeathUpdate::Timer(_))));
+        // when hit by several characters at once, the highest id gets the kill so every world agrees
+        let hit = changes.iter()
+            .filter_map(|change| match change {
+                CDeath(DeathUpdate::Hit(source)) => Some(*source),
+                _ => None,
+            })
+            .max();
+        Ok(state.cloned().into_iter()
+            .chain(hit.map(|source| CDeath(DeathUpdate::Hit(source))))
+            .collect())
+    }
+}
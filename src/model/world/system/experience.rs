@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+
+use itertools::Itertools;
+use nalgebra::Vector2;
+use serde::{Serialize, Deserialize};
+
+use crate::model::world::{component::{sum_changes, Component, ComponentUpdateData, GetComponentID, ComponentID, ComponentUpdate, ComponentStorageContainer, ComponentStorageCommon}, WorldSystem, WorldError, WorldInfo, ComponentSystem, commands::{CharacterCommand, WorldCommand}, character::CharacterID, World, Update, CharacterCommandState, WorldErrorI};
+
+use super::{modifiers::Stat, health::CharacterHealthUpdate, death::is_alive, team::are_allies};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExperienceInfo {
+    pub thresholds: Vec<f32>, // total xp needed for each level after the first
+    pub growth: BTreeMap<Stat, f32>, // added to the base stat for each level after the first
+    pub health_growth: f32, // added to max health for each level after the first
+}
+
+// what a character is worth to the enemies around it when it dies
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct BountyInfo {
+    pub xp: f32, // split between everyone in the radius
+    pub radius: f32,
+    pub gold: f32, // all to whoever got the kill
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Experience {
+    pub xp: f32, // total, doesn't go back to 0 on level up
+    pub level: u32, // starts at 1
+}
+
+impl Default for Experience {
+    fn default() -> Self {
+        Self { xp: 0.0, level: 1 }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum ExperienceUpdate {
+    New,
+    Gain(f32),
+    LevelUp,
+}
+
+impl Component for Experience {
+    fn update(&self, update: &ComponentUpdateData) -> Self {
+        match *update {
+            ComponentUpdateData::Experience(ExperienceUpdate::New) => Self::default(),
+            ComponentUpdateData::Experience(ExperienceUpdate::Gain(xp)) => Self { xp: self.xp + xp, level: self.level },
+            ComponentUpdateData::Experience(ExperienceUpdate::LevelUp) => Self { xp: self.xp, level: self.level + 1 },
+            _ => *self
+        }
+    }
+}
+
+impl GetComponentID for Experience {
+    const ID: ComponentID = ComponentID::Experience;
+}
+
+pub fn get_level(world: &World, cid: &CharacterID) -> u32 {
+    world.experience.get_component(cid).map(|experience| experience.level).unwrap_or(1)
+}
+
+// how much a stat has grown from leveling, added on top of the base stat
+pub fn level_growth(world: &World, cid: &CharacterID, stat: Stat) -> f32 {
+    let level = get_level(world, cid);
+    world.base.get_component(cid).ok()
+        .and_then(|base| world.info.experience.get(&base.ctype))
+        .and_then(|info| info.growth.get(&stat))
+        .map(|growth| growth * (level - 1) as f32)
+        .unwrap_or(0.0)
+}
+
+// the dead character's bounty, split between the enemies near it that can level
+pub fn make_kill_experience_updates(world: &World, target: &CharacterID) -> Vec<Update> {
+    let (bounty, pos) = match world.base.get_component(target) {
+        Ok(base) => match world.info.bounty.get(&base.ctype) {
+            Some(bounty) if bounty.xp > 0.0 => (bounty, Vector2::new(base.position.x, base.position.y)),
+            _ => return vec![],
+        },
+        Err(_) => return vec![],
+    };
+    let receivers = world.experience.get_characters().into_iter()
+        .sorted()
+        .filter(|cid| *cid != *target && is_alive(world, cid) && !are_allies(world, cid, target))
+        .filter(|cid| world.base.get_component(cid)
+            .map(|base| (Vector2::new(base.position.x, base.position.y) - pos).magnitude() <= bounty.radius)
+            .unwrap_or(false))
+        .collect_vec();
+    let xp = bounty.xp / receivers.len().max(1) as f32;
+    receivers.into_iter()
+        .map(|cid| Update::Comp(ComponentUpdate {
+            cid,
+            data: ComponentUpdateData::Experience(ExperienceUpdate::Gain(xp)),
+        }))
+        .collect()
+}
+
+pub struct ExperienceSystem;
+
+impl WorldSystem for ExperienceSystem {
+    fn init_world_info(&self) -> Result<WorldInfo, WorldError> {
+        Ok(WorldInfo::new())
+    }
+}
+
+impl ComponentSystem for ExperienceSystem {
+    fn get_component_id(&self) -> ComponentID {
+        ComponentID::Experience
+    }
+
+    fn validate_character_command(&self, _: &World, _: &CharacterID, _: &CharacterCommand) -> Result<CharacterCommandState, WorldError> {
+        Err(WorldErrorI::InvalidCommandMapping.err())
+    }
+
+    // go up a level once there is enough xp for it, one level per tick
+    fn update_character(&self, world: &World, _: &[WorldCommand], cid: &CharacterID, _: f32) -> Result<Vec<Update>, WorldError> {
+        let experience = world.experience.get_component(cid)?;
+        let ctype = world.base.get_component(cid)?.ctype;
+        let info = world.info.experience.get(&ctype)
+            .ok_or_else(|| WorldErrorI::MissingCharacterInfoComponent(ctype, ComponentID::Experience).err())?;
+        match info.thresholds.get(experience.level as usize - 1) {
+            Some(threshold) if experience.xp >= *threshold => Ok([
+                Some(ComponentUpdateData::Experience(ExperienceUpdate::LevelUp)),
+                (info.health_growth != 0.0 && world.health.get_component(cid).is_ok())
+                    .then_some(ComponentUpdateData::Health(CharacterHealthUpdate::Grow(info.health_growth))),
+            ].into_iter()
+            .flatten()
+            .map(|data| Update::Comp(ComponentUpdate { cid: *cid, data }))
+            .collect()),
+            _ => Ok(vec![]),
+        }
+    }
+
+    fn reduce_changes(&self, cid: &CharacterID, world: &World, changes: &[ComponentUpdateData]) -> Result<Vec<ComponentUpdateData>, WorldError> {
+        if !world.characters.contains(cid) {
+            let new_changes: Vec<ComponentUpdateData> = changes.iter()
+                .filter(|new| matches!(*new, ComponentUpdateData::Experience(ExperienceUpdate::New)))
+                .cloned().collect();
+            if new_changes.is_empty() {
+                return Err(WorldErrorI::InvalidReduceMapping(*cid, ComponentID::Experience).err())
+            } else if new_changes.len() > 1 {
+                return Err(WorldErrorI::MultipleUpdateOverrides(*cid, ComponentID::Experience).err())
+            } else {
+                return Ok(new_changes)
+            }
+        }
+        let gain = sum_changes(changes.iter()
+            .filter_map(|change| match *change {
+                ComponentUpdateData::Experience(ExperienceUpdate::Gain(xp)) => Some(xp),
+                _ => None,
+            }));
+        // level ups only come from the character's own update, so there is at most one
+        let level_up = changes.iter()
+            .find(|change| matches!(*change, ComponentUpdateData::Experience(ExperienceUpdate::LevelUp)))
+            .cloned();
+        Ok(gain.map(|xp| ComponentUpdateData::Experience(ExperienceUpdate::Gain(xp))).into_iter()
+           .chain(level_up)
+           .collect())
+    }
+}
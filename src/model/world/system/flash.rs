@@ -92,7 +92,9 @@ impl ComponentSystem for FlashAbilitySystem {
         match cmd {
             CharacterCommand::Flash(cmd) => {
                 let flash = world.flash.get_component(cid)?;
-                flash.ability.validate_command(world, cid, &cmd.to_ability_command(world, cid)?)
+                let ctype = world.base.get_component(cid)?.ctype;
+                let info = world.info.flash.get(&ctype).ok_or_else(|| WorldErrorI::MissingCharacterInfoComponent(ctype, ComponentID::Flash).err())?;
+                flash.ability.validate_command(world, &info.ability, cid, &cmd.to_ability_command(world, cid)?)
             },
             _ => Err(WorldErrorI::InvalidCommandMapping.err())
         }
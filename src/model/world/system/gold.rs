@@ -0,0 +1,115 @@
+use serde::{Serialize, Deserialize};
+
+use crate::model::world::{component::{sum_changes, Component, ComponentUpdateData, GetComponentID, ComponentID, ComponentUpdate, ComponentStorageContainer}, WorldSystem, WorldError, WorldInfo, ComponentSystem, commands::{CharacterCommand, WorldCommand}, character::CharacterID, World, Update, CharacterCommandState, WorldErrorI};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct GoldInfo {
+    pub starting: f32,
+    pub income: f32, // per second
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct Gold {
+    pub gold: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum GoldUpdate {
+    New(f32),
+    Change(f32),
+}
+
+impl Component for Gold {
+    fn update(&self, update: &ComponentUpdateData) -> Self {
+        match *update {
+            ComponentUpdateData::Gold(GoldUpdate::New(gold)) => Self { gold },
+            ComponentUpdateData::Gold(GoldUpdate::Change(delta)) => Self { gold: self.gold + delta },
+            _ => *self
+        }
+    }
+}
+
+impl GetComponentID for Gold {
+    const ID: ComponentID = ComponentID::Gold;
+}
+
+pub fn has_gold(world: &World, cid: &CharacterID, cost: f32) -> bool {
+    world.gold.get_component(cid)
+        .map(|gold| gold.gold >= cost)
+        .unwrap_or(false)
+}
+
+pub fn make_gold_update(cid: &CharacterID, delta: f32) -> Update {
+    Update::Comp(ComponentUpdate {
+        cid: *cid,
+        data: ComponentUpdateData::Gold(GoldUpdate::Change(delta)),
+    })
+}
+
+// the dead character's gold bounty goes to whoever got the kill
+pub fn make_kill_gold_updates(world: &World, target: &CharacterID, killer: Option<&CharacterID>) -> Vec<Update> {
+    let bounty = world.base.get_component(target).ok()
+        .and_then(|base| world.info.bounty.get(&base.ctype))
+        .map(|bounty| bounty.gold)
+        .unwrap_or(0.0);
+    killer
+        .filter(|killer| bounty > 0.0 && world.gold.get_component(killer).is_ok())
+        .map(|killer| make_gold_update(killer, bounty))
+        .into_iter()
+        .collect()
+}
+
+pub struct GoldSystem;
+
+impl WorldSystem for GoldSystem {
+    fn init_world_info(&self) -> Result<WorldInfo, WorldError> {
+        Ok(WorldInfo::new())
+    }
+}
+
+impl ComponentSystem for GoldSystem {
+    fn get_component_id(&self) -> ComponentID {
+        ComponentID::Gold
+    }
+
+    fn validate_character_command(&self, _: &World, _: &CharacterID, _: &CharacterCommand) -> Result<CharacterCommandState, WorldError> {
+        Err(WorldErrorI::InvalidCommandMapping.err())
+    }
+
+    // passive income
+    fn update_character(&self, world: &World, _: &[WorldCommand], cid: &CharacterID, delta_time: f32) -> Result<Vec<Update>, WorldError> {
+        let ctype = world.base.get_component(cid)?.ctype;
+        let info = world.info.gold.get(&ctype)
+            .ok_or_else(|| WorldErrorI::MissingCharacterInfoComponent(ctype, ComponentID::Gold).err())?;
+        if info.income <= 0.0 {
+            return Ok(vec![]);
+        }
+        Ok(vec![make_gold_update(cid, info.income * delta_time)])
+    }
+
+    fn reduce_changes(&self, cid: &CharacterID, world: &World, changes: &[ComponentUpdateData]) -> Result<Vec<ComponentUpdateData>, WorldError> {
+        if !world.characters.contains(cid) {
+            let new_changes: Vec<ComponentUpdateData> = changes.iter()
+                .filter(|new| matches!(*new, ComponentUpdateData::Gold(GoldUpdate::New(_))))
+                .cloned().collect();
+            if new_changes.is_empty() {
+                return Err(WorldErrorI::InvalidReduceMapping(*cid, ComponentID::Gold).err())
+            } else if new_changes.len() > 1 {
+                return Err(WorldErrorI::MultipleUpdateOverrides(*cid, ComponentID::Gold).err())
+            } else {
+                return Ok(new_changes)
+            }
+        }
+        let change = sum_changes(changes.iter()
+            .filter_map(|change| match *change {
+                ComponentUpdateData::Gold(GoldUpdate::Change(delta)) => Some(delta),
+                _ => None,
+            }));
+        // gold can't go below 0
+        let gold = world.gold.get_component(cid)?;
+        Ok(change.into_iter()
+           .map(|change| f32::max(gold.gold + change, 0.0) - gold.gold)
+           .map(|change| ComponentUpdateData::Gold(GoldUpdate::Change(change)))
+           .collect())
+    }
+}
@@ -0,0 +1,77 @@
+use std::path::PathBuf;
+use nalgebra::Vector2;
+use serde::{Serialize, Deserialize};
+use image::io::Reader as ImageReader;
+
+// mask pixels at least this opaque put their layer's terrain type in the cell
+const MASK_THRESHOLD: u8 = 128;
+
+// the type of terrain in each cell of the map, taken from the map's layer masks
+// later layers are drawn over earlier ones, so they win where masks overlap
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct GroundGrid {
+    pub size: Vector2<i32>,
+    pub cell_size: Vector2<f32>,
+    world_size: f32,
+    types: Vec<Option<usize>>, // the layer in each cell, if any
+    speeds: Vec<f32>, // movement multiplier of each layer
+}
+
+impl GroundGrid {
+    // layers are the mask image and movement multiplier of each layer, in drawing order
+    // every mask has to be the same size
+    pub fn load(world_size: f32, layers: &[(PathBuf, f32)]) -> Result<Self, String> {
+        let mut size = Vector2::new(1, 1);
+        let mut types = vec![None];
+        for (layer, (path, _)) in layers.iter().enumerate() {
+            let img = ImageReader::open(path).map_err(|err| format!("{}: {}", path.display(), err))?
+                .decode().map_err(|err| format!("{}: {}", path.display(), err))?
+                .to_rgba8();
+            let img_size = Vector2::new(img.width() as i32, img.height() as i32);
+            if layer == 0 {
+                size = img_size;
+                types = vec![None; (size.x * size.y) as usize];
+            } else if img_size != size {
+                return Err(format!("{} is {}x{} but the first layer mask is {}x{}",
+                    path.display(), img_size.x, img_size.y, size.x, size.y));
+            }
+            for j in 0..img.height() {
+                for i in 0..img.width() {
+                    if img[(i, j)].0[3] >= MASK_THRESHOLD {
+                        types[(j * img.width() + i) as usize] = Some(layer);
+                    }
+                }
+            }
+        }
+        Ok(Self {
+            size,
+            cell_size: Vector2::new(world_size / size.x as f32, world_size / size.y as f32),
+            world_size,
+            types,
+            speeds: layers.iter().map(|(_, speed)| *speed).collect(),
+        })
+    }
+
+    pub fn to_cell(&self, pos: &Vector2<f32>) -> Vector2<i32> {
+        Vector2::new(
+            ((pos.x + self.world_size / 2.0) / self.cell_size.x).floor() as i32,
+            ((pos.y + self.world_size / 2.0) / self.cell_size.y).floor() as i32,
+        )
+    }
+
+    // the movement multiplier at a position, 1 where no layer covers the ground
+    pub fn speed(&self, pos: &Vector2<f32>) -> f32 {
+        let cell = self.to_cell(pos);
+        if cell.x < 0 || cell.y < 0 || cell.x >= self.size.x || cell.y >= self.size.y {
+            return 1.0;
+        }
+        self.types[(cell.y * self.size.x + cell.x) as usize]
+            .map(|layer| self.speeds[layer])
+            .unwrap_or(1.0)
+    }
+
+    // ground with no movement can't be walked onto, ex: lava
+    pub fn passable(&self, pos: &Vector2<f32>) -> bool {
+        self.speed(pos) > 0.0
+    }
+}
@@ -1,6 +1,8 @@
 use serde::{Serialize, Deserialize};
 
-use crate::model::world::{component::{Component, ComponentUpdateData, GetComponentID, ComponentID, ComponentUpdate}, WorldSystem, WorldError, WorldInfo, ComponentSystem, commands::{CharacterCommand, WorldCommand}, character::CharacterID, World, Update, CharacterCommandState, WorldErrorI};
+use crate::model::world::{component::{Component, ComponentUpdateData, GetComponentID, ComponentID, ComponentUpdate, ComponentStorageContainer}, WorldSystem, WorldError, WorldInfo, ComponentSystem, commands::{CharacterCommand, WorldCommand}, character::CharacterID, World, Update, CharacterCommandState, WorldErrorI, WorldEvent};
+
+use super::{death::make_hit_update, modifiers::{effective_stat, Stat}};
 
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
@@ -18,6 +20,13 @@ impl Component for CharacterHealth {
             ComponentUpdateData::Health(CharacterHealthUpdate::Change(x)) => {
                 Self { health: self.health + x, max_health: self.max_health }
             },
+            ComponentUpdateData::Health(CharacterHealthUpdate::Damage(ref damage)) => {
+                Self { health: self.health - damage.amount, max_health: self.max_health }
+            },
+            ComponentUpdateData::Health(CharacterHealthUpdate::Grow(x)) if self.max_health > 0.0 => {
+                let max_health = self.max_health + x;
+                Self { health: self.health * max_health / self.max_health, max_health }
+            },
             _ => *self
         }
     }
@@ -25,8 +34,31 @@ impl Component for CharacterHealth {
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum CharacterHealthUpdate {
-    Change(f32), // todo: add cause
+    Change(f32), // healing and other changes that don't come from anyone
+    Damage(DamageEvent), // amount is after mitigation
     New(f32),
+    Grow(f32), // change to max health, which changes health by the same proportion
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DamageKind {
+    Physical, // reduced by armor
+    Magic, // reduced by magic resist
+    True,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DamageEvent {
+    pub source: CharacterID,
+    pub target: CharacterID,
+    pub kind: DamageKind,
+    pub amount: f32,
+}
+
+// each point of resistance takes away a smaller part of the damage, so stacking it never makes
+// anyone immune
+pub fn mitigate(amount: f32, resistance: f32) -> f32 {
+    amount * 100.0 / (100.0 + f32::max(resistance, 0.0))
 }
 
 impl GetComponentID for CharacterHealth {
@@ -58,6 +90,36 @@ pub fn make_health_update(cid: &CharacterID, health_change: f32) -> Update {
     )
 }
 
+// the damage a target actually takes, after its resistances
+pub fn mitigated_damage(world: &World, target: &CharacterID, kind: DamageKind, amount: f32) -> Result<f32, WorldError> {
+    Ok(match kind {
+        DamageKind::Physical => mitigate(amount, effective_stat(world, target, Stat::Armor)?),
+        DamageKind::Magic => mitigate(amount, effective_stat(world, target, Stat::MagicResist)?),
+        DamageKind::True => amount,
+    })
+}
+
+// damage a character by an amount before armor and magic resist
+// the event in the update and on the world afterwards has the amount after mitigation
+pub fn make_damage_updates(world: &World, source: &CharacterID, target: &CharacterID, kind: DamageKind, amount: f32) -> Result<Vec<Update>, WorldError> {
+    let amount = mitigated_damage(world, target, kind, amount)?;
+    let event = DamageEvent {
+        source: *source,
+        target: *target,
+        kind,
+        amount,
+    };
+    Ok([
+        Some(Update::Comp(ComponentUpdate {
+            cid: *target,
+            data: ComponentUpdateData::Health(CharacterHealthUpdate::Damage(event.clone())),
+        })),
+        Some(Update::Event(WorldEvent::Damage(event))),
+        // the death system gives the kill to whoever hit last
+        world.death.get_component(target).ok().map(|_| make_hit_update(target, source)),
+    ].into_iter().flatten().collect())
+}
+
 impl ComponentSystem for HealthSystem {
     fn get_component_id(&self) -> ComponentID {
         ComponentID::Health
@@ -83,16 +145,27 @@ impl ComponentSystem for HealthSystem {
             }
         }
         let mut current = None;
+        let mut grow = None;
         for change in changes {
             if let ComponentUpdateData::Health(change) = change.clone() {
                 match change {
                     CharacterHealthUpdate::New(_health) => (), // ignore, only applies if cid is new
-                    CharacterHealthUpdate::Change(delta) => current = Some(current.unwrap_or(0.0) + delta)
+                    CharacterHealthUpdate::Change(delta) => current = Some(current.unwrap_or(0.0) + delta),
+                    CharacterHealthUpdate::Damage(damage) => current = Some(current.unwrap_or(0.0) - damage.amount),
+                    CharacterHealthUpdate::Grow(delta) => grow = Some(grow.unwrap_or(0.0) + delta),
                 }
             }
         }
-        Ok(current.into_iter()
-           .map(|change| ComponentUpdateData::Health(CharacterHealthUpdate::Change(change)))
+        // max health grows first, then health is kept between 0 and the new max health
+        let health = world.health.get_component(cid)?;
+        let health = match grow {
+            Some(delta) => health.update(&ComponentUpdateData::Health(CharacterHealthUpdate::Grow(delta))),
+            None => *health,
+        };
+        Ok(grow.map(|delta| ComponentUpdateData::Health(CharacterHealthUpdate::Grow(delta))).into_iter()
+           .chain(current.into_iter()
+               .map(|change| (health.health + change).clamp(0.0, health.max_health) - health.health)
+               .map(|change| ComponentUpdateData::Health(CharacterHealthUpdate::Change(change))))
            .collect())
     }
 }
@@ -0,0 +1,268 @@
+use nalgebra::Vector2;
+use serde::{Serialize, Deserialize};
+
+use crate::model::{world::{component::{Component, ComponentUpdateData, GetComponentID, ComponentID, ComponentUpdate, ComponentStorageContainer}, WorldSystem, WorldError, WorldInfo, ComponentSystem, commands::{CharacterCommand, WorldCommand}, character::CharacterID, World, Update, CharacterCommandState, WorldErrorI}, commands::GetCommandID};
+
+use super::{modifiers::{Stat, ModifierValue, Modifier, ModifiersUpdate}, gold::{has_gold, make_gold_update}};
+
+pub const INVENTORY_SLOTS: usize = 6;
+
+// part of the cost given back when an item is sold
+pub const SELL_RATIO: f32 = 0.7;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ItemInfo {
+    pub cost: f32,
+    pub modifiers: Vec<(Stat, ModifierValue)>,
+}
+
+// where characters can buy and sell
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct ShopZone {
+    pub position: Vector2<f32>,
+    pub radius: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Inventory {
+    pub items: Vec<Option<String>>, // item names, one per slot
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self { items: vec![None; INVENTORY_SLOTS] }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum InventoryUpdate {
+    New,
+    Set(usize, Option<String>),
+}
+
+impl Component for Inventory {
+    fn update(&self, update: &ComponentUpdateData) -> Self {
+        let mut next = self.clone();
+        if let ComponentUpdateData::Inventory(update) = update {
+            match update {
+                InventoryUpdate::New => next = Inventory::default(),
+                InventoryUpdate::Set(slot, item) => if let Some(current) = next.items.get_mut(*slot) {
+                    *current = item.clone();
+                },
+            }
+        }
+        next
+    }
+}
+
+impl GetComponentID for Inventory {
+    const ID: ComponentID = ComponentID::Inventory;
+}
+
+// items are permanent modifiers from the character itself, removed again when sold
+fn item_modifiers(cid: &CharacterID, item: &ItemInfo) -> Vec<Modifier> {
+    item.modifiers.iter()
+        .map(|(stat, value)| Modifier {
+            stat: *stat,
+            value: *value,
+            source: *cid,
+            expiry: None,
+        })
+        .collect()
+}
+
+fn get_item<'a>(world: &'a World, item: &str) -> Result<&'a ItemInfo, WorldError> {
+    world.info.items.get(item).ok_or_else(|| WorldErrorI::UnknownItem(item.to_string()).err())
+}
+
+pub fn in_shop(world: &World, cid: &CharacterID) -> bool {
+    world.base.get_component(cid)
+        .map(|base| world.info.shop_zones.iter().any(|zone|
+            (Vector2::new(base.position.x, base.position.y) - zone.position).magnitude() <= zone.radius))
+        .unwrap_or(false)
+}
+
+fn validate_shop_command(world: &World, cid: &CharacterID, cmd: &ShopCommand) -> Result<(), WorldError> {
+    let inventory = world.inventory.get_component(cid)?;
+    if !in_shop(world, cid) {
+        return Err(WorldErrorI::NotInShop(*cid).err());
+    }
+    match cmd {
+        ShopCommand::Buy(item) => {
+            let info = get_item(world, item)?;
+            if !has_gold(world, cid, info.cost) {
+                return Err(WorldErrorI::InsufficientGold(*cid).err());
+            }
+            if !inventory.items.iter().any(Option::is_none) {
+                return Err(WorldErrorI::InventoryFull(*cid).err());
+            }
+            Ok(())
+        },
+        ShopCommand::Sell(slot) => match inventory.items.get(*slot) {
+            Some(Some(item)) => get_item(world, item).map(|_| ()),
+            _ => Err(WorldErrorI::EmptySlot(*cid, *slot).err()),
+        },
+    }
+}
+
+fn shop_updates(world: &World, cid: &CharacterID, cmd: &ShopCommand) -> Result<Vec<Update>, WorldError> {
+    let inventory = world.inventory.get_component(cid)?;
+    let comp = |data| Update::Comp(ComponentUpdate { cid: *cid, data });
+    match cmd {
+        ShopCommand::Buy(item) => {
+            let info = get_item(world, item)?;
+            let slot = inventory.items.iter().position(Option::is_none)
+                .ok_or_else(|| WorldErrorI::InventoryFull(*cid).err())?;
+            Ok([
+                comp(ComponentUpdateData::Inventory(InventoryUpdate::Set(slot, Some(item.clone())))),
+                make_gold_update(cid, -info.cost),
+            ].into_iter()
+            .chain(item_modifiers(cid, info).into_iter()
+                .map(|modifier| comp(ComponentUpdateData::Modifiers(ModifiersUpdate::Add(modifier)))))
+            .collect())
+        },
+        ShopCommand::Sell(slot) => {
+            let item = inventory.items.get(*slot).cloned().flatten()
+                .ok_or_else(|| WorldErrorI::EmptySlot(*cid, *slot).err())?;
+            let info = get_item(world, &item)?;
+            Ok([
+                comp(ComponentUpdateData::Inventory(InventoryUpdate::Set(*slot, None))),
+                make_gold_update(cid, info.cost * SELL_RATIO),
+            ].into_iter()
+            .chain(item_modifiers(cid, info).into_iter()
+                .map(|modifier| comp(ComponentUpdateData::Modifiers(ModifiersUpdate::Remove(modifier)))))
+            .collect())
+        },
+    }
+}
+
+pub struct InventorySystem;
+
+impl WorldSystem for InventorySystem {
+    fn init_world_info(&self) -> Result<WorldInfo, WorldError> {
+        Ok(WorldInfo::new())
+    }
+}
+
+impl ComponentSystem for InventorySystem {
+    fn get_component_id(&self) -> ComponentID {
+        ComponentID::Inventory
+    }
+
+    fn validate_character_command(&self, world: &World, cid: &CharacterID, cmd: &CharacterCommand) -> Result<CharacterCommandState, WorldError> {
+        match cmd {
+            CharacterCommand::Shop(cmd) => {
+                validate_shop_command(world, cid, cmd)?;
+                Ok(CharacterCommandState::Ready)
+            },
+            _ => Err(WorldErrorI::InvalidCommandMapping.err())
+        }
+    }
+
+    // one purchase or sale per tick, checked again since gold may have been spent since validation
+    fn update_character(&self, world: &World, commands: &[WorldCommand], cid: &CharacterID, _: f32) -> Result<Vec<Update>, WorldError> {
+        let command = commands.iter()
+            .filter_map(|cmd| match cmd {
+                WorldCommand::CharacterComponent(ccid, ComponentID::Inventory, CharacterCommand::Shop(cmd)) if *ccid == *cid => Some(cmd),
+                _ => None,
+            })
+            .find(|cmd| validate_shop_command(world, cid, cmd).is_ok());
+        match command {
+            Some(cmd) => shop_updates(world, cid, cmd),
+            None => Ok(vec![]),
+        }
+    }
+
+    fn reduce_changes(&self, cid: &CharacterID, world: &World, changes: &[ComponentUpdateData]) -> Result<Vec<ComponentUpdateData>, WorldError> {
+        if !world.characters.contains(cid) {
+            let new_changes: Vec<ComponentUpdateData> = changes.iter()
+                .filter(|new| matches!(*new, ComponentUpdateData::Inventory(InventoryUpdate::New)))
+                .cloned().collect();
+            if new_changes.is_empty() {
+                return Err(WorldErrorI::InvalidReduceMapping(*cid, ComponentID::Inventory).err())
+            } else if new_changes.len() > 1 {
+                return Err(WorldErrorI::MultipleUpdateOverrides(*cid, ComponentID::Inventory).err())
+            } else {
+                return Ok(new_changes)
+            }
+        }
+        // slots only change from the character's own update, which does one thing per tick
+        let sets: Vec<ComponentUpdateData> = changes.iter()
+            .filter(|change| matches!(*change, ComponentUpdateData::Inventory(InventoryUpdate::Set(_, _))))
+            .cloned()
+            .collect();
+        if sets.len() > 1 {
+            return Err(WorldErrorI::MultipleUpdateOverrides(*cid, ComponentID::Inventory).err())
+        }
+        Ok(sets)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum ShopCommand {
+    Buy(String), // item name
+    Sell(usize), // inventory slot
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BuyRequest {
+    pub user: CharacterID,
+    pub item: String,
+}
+
+impl GetCommandID for BuyRequest {
+    fn command_id(&self) -> crate::model::commands::CommandID {
+        crate::model::commands::CommandID::BuyRequest
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SellRequest {
+    pub user: CharacterID,
+    pub slot: usize,
+}
+
+impl GetCommandID for SellRequest {
+    fn command_id(&self) -> crate::model::commands::CommandID {
+        crate::model::commands::CommandID::SellRequest
+    }
+}
+
+#[cfg(feature = "server")]
+pub mod server {
+    use std::net::SocketAddr;
+
+    use crate::{model::{player::{server::PlayerCommand, model::{PlayerID, PlayerDataView}, commands::ChatMessage}, PrintError, world::{component::ComponentID, commands::{WorldCommand, CharacterCommand}, character::CharacterID}}, server::{commands::{ProtocolSpec, SendCommands}, main::Server}, networking::Protocol};
+
+    use super::{BuyRequest, SellRequest, ShopCommand};
+
+    // the world checks the shop zone and the cost before the command runs
+    fn run_shop_command(addr: &SocketAddr, player_id: &PlayerID, server: &mut Server, user: CharacterID, cmd: ShopCommand) {
+        if server.player_manager.can_use_character(player_id, &user) {
+            server.run_world_command(
+                Some(addr),
+                WorldCommand::CharacterComponent(user, ComponentID::Inventory, CharacterCommand::Shop(cmd))
+            );
+        } else {
+            server.connection.send(
+                Protocol::TCP,
+                addr,
+                &ChatMessage("Error: no permission".to_string())
+            ).print()
+        }
+    }
+
+    impl<'a> PlayerCommand<'a> for BuyRequest {
+        const PROTOCOL: ProtocolSpec = ProtocolSpec::One(Protocol::TCP);
+        fn run(self, addr: &SocketAddr, player_id: &PlayerID, server: &mut Server) {
+            run_shop_command(addr, player_id, server, self.user, ShopCommand::Buy(self.item));
+        }
+    }
+
+    impl<'a> PlayerCommand<'a> for SellRequest {
+        const PROTOCOL: ProtocolSpec = ProtocolSpec::One(Protocol::TCP);
+        fn run(self, addr: &SocketAddr, player_id: &PlayerID, server: &mut Server) {
+            run_shop_command(addr, player_id, server, self.user, ShopCommand::Sell(self.slot));
+        }
+    }
+}
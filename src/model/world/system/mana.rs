@@ -0,0 +1,137 @@
+use itertools::Itertools;
+use serde::{Serialize, Deserialize};
+
+use crate::model::world::{component::{sum_changes, Component, ComponentUpdateData, GetComponentID, ComponentID, ComponentUpdate, ComponentStorageContainer}, WorldSystem, WorldError, WorldInfo, ComponentSystem, commands::{CharacterCommand, WorldCommand}, character::CharacterID, World, Update, CharacterCommandState, WorldErrorI};
+
+use super::status::StatusID;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct ManaInfo {
+    pub max_mana: f32,
+    pub regen: f32, // per second
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct Mana {
+    pub mana: f32,
+    pub max_mana: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum ManaUpdate {
+    New(f32),
+    Change(f32),
+}
+
+impl Component for Mana {
+    fn update(&self, update: &ComponentUpdateData) -> Self {
+        match *update {
+            ComponentUpdateData::Mana(ManaUpdate::New(max_mana)) => Self { mana: max_mana, max_mana },
+            ComponentUpdateData::Mana(ManaUpdate::Change(delta)) => Self { mana: self.mana + delta, max_mana: self.max_mana },
+            _ => *self
+        }
+    }
+}
+
+impl GetComponentID for Mana {
+    const ID: ComponentID = ComponentID::Mana;
+}
+
+// characters without mana don't pay for abilities
+pub fn has_mana(world: &World, cid: &CharacterID, cost: f32) -> bool {
+    cost <= 0.0 || world.mana.get_component(cid)
+        .map(|mana| mana.mana >= cost)
+        .unwrap_or(true)
+}
+
+// whether the character can pay for its cast of an ability that fires in this update
+// casts of different abilities can fire in the same tick, so they are paid for in StatusID order,
+// and the ones the character can't afford after paying for the earlier ones don't fire
+pub fn can_afford_fire(world: &World, cid: &CharacterID, status_id: StatusID, cost: f32, delta_time: f32) -> Result<bool, WorldError> {
+    let ctype = world.base.get_component(cid)?.ctype;
+    let firing = [
+        world.flash.get_component(cid).ok().zip(world.info.flash.get(&ctype))
+            .map(|(flash, info)| (flash.ability.status_id, flash.ability.fires(world, &info.ability, delta_time), info.ability.cost)),
+        world.aoe.get_component(cid).ok().zip(world.info.aoe.get(&ctype))
+            .map(|(aoe, info)| (aoe.ability.status_id, aoe.ability.fires(world, &info.ability, delta_time), info.ability.cost)),
+        world.bolt.get_component(cid).ok().zip(world.info.bolt.get(&ctype))
+            .map(|(bolt, info)| (bolt.ability.status_id, bolt.ability.fires(world, &info.ability, delta_time), info.ability.cost)),
+    ];
+    let mut spent = 0.0;
+    for (id, _, other_cost) in firing.into_iter().flatten().filter(|(_, fires, _)| *fires).sorted_by_key(|(id, _, _)| *id) {
+        let affordable = has_mana(world, cid, spent + other_cost);
+        if id == status_id {
+            return Ok(affordable);
+        }
+        if affordable {
+            spent += other_cost;
+        }
+    }
+    Ok(has_mana(world, cid, spent + cost))
+}
+
+pub fn make_mana_cost_update(world: &World, cid: &CharacterID, cost: f32) -> Option<Update> {
+    (cost > 0.0 && world.mana.get_component(cid).is_ok()).then_some(Update::Comp(ComponentUpdate {
+        cid: *cid,
+        data: ComponentUpdateData::Mana(ManaUpdate::Change(-cost)),
+    }))
+}
+
+pub struct ManaSystem;
+
+impl WorldSystem for ManaSystem {
+    fn init_world_info(&self) -> Result<WorldInfo, WorldError> {
+        Ok(WorldInfo::new())
+    }
+}
+
+impl ComponentSystem for ManaSystem {
+    fn get_component_id(&self) -> ComponentID {
+        ComponentID::Mana
+    }
+
+    fn validate_character_command(&self, _: &World, _: &CharacterID, _: &CharacterCommand) -> Result<CharacterCommandState, WorldError> {
+        Err(WorldErrorI::InvalidCommandMapping.err())
+    }
+
+    // regenerate up to the max
+    fn update_character(&self, world: &World, _: &[WorldCommand], cid: &CharacterID, delta_time: f32) -> Result<Vec<Update>, WorldError> {
+        let mana = world.mana.get_component(cid)?;
+        let ctype = world.base.get_component(cid)?.ctype;
+        let info = world.info.mana.get(&ctype)
+            .ok_or_else(|| WorldErrorI::MissingCharacterInfoComponent(ctype, ComponentID::Mana).err())?;
+        if mana.mana >= mana.max_mana || info.regen <= 0.0 {
+            return Ok(vec![]);
+        }
+        Ok(vec![Update::Comp(ComponentUpdate {
+            cid: *cid,
+            data: ComponentUpdateData::Mana(ManaUpdate::Change(info.regen * delta_time)),
+        })])
+    }
+
+    fn reduce_changes(&self, cid: &CharacterID, world: &World, changes: &[ComponentUpdateData]) -> Result<Vec<ComponentUpdateData>, WorldError> {
+        if !world.characters.contains(cid) {
+            let new_changes: Vec<ComponentUpdateData> = changes.iter()
+                .filter(|new| matches!(*new, ComponentUpdateData::Mana(ManaUpdate::New(_))))
+                .cloned().collect();
+            if new_changes.is_empty() {
+                return Err(WorldErrorI::InvalidReduceMapping(*cid, ComponentID::Mana).err())
+            } else if new_changes.len() > 1 {
+                return Err(WorldErrorI::MultipleUpdateOverrides(*cid, ComponentID::Mana).err())
+            } else {
+                return Ok(new_changes)
+            }
+        }
+        let change = sum_changes(changes.iter()
+            .filter_map(|change| match *change {
+                ComponentUpdateData::Mana(ManaUpdate::Change(delta)) => Some(delta),
+                _ => None,
+            }));
+        // keep mana between 0 and max mana
+        let mana = world.mana.get_component(cid)?;
+        Ok(change.into_iter()
+           .map(|change| (mana.mana + change).clamp(0.0, mana.max_mana) - mana.mana)
+           .map(|change| ComponentUpdateData::Mana(ManaUpdate::Change(change)))
+           .collect())
+    }
+}
@@ -8,4 +8,16 @@ pub mod health;
 pub mod status;
 pub mod ability;
 pub mod flash;
-pub mod collision;
\ No newline at end of file
+pub mod collision;
+pub mod pathfinding;
+pub mod ground;
+pub mod death;
+pub mod modifiers;
+pub mod aoe;
+pub mod bolt;
+pub mod mana;
+pub mod team;
+pub mod tower;
+pub mod experience;
+pub mod gold;
+pub mod inventory;
\ No newline at end of file
@@ -0,0 +1,189 @@
+use serde::{Serialize, Deserialize};
+
+use crate::model::{world::{World, character::CharacterID, component::{GetComponentID, ComponentID, ComponentUpdateData, Component, ComponentUpdate, ComponentStorageContainer}, WorldError, WorldInfo, WorldSystem, commands::{CharacterCommand, WorldCommand}, ComponentSystem, Update, CharacterCommandState, WorldErrorI}, WorldTick};
+
+use super::{base::CharacterBase, experience::level_growth};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Stat {
+    Speed,
+    AttackDamage,
+    Range,
+    AttackSpeed,
+    Armor,
+    MagicResist,
+}
+
+impl Stat {
+    pub fn base_value(&self, base: &CharacterBase) -> f32 {
+        match *self {
+            Stat::Speed => base.speed,
+            Stat::AttackDamage => base.attack_damage,
+            Stat::Range => base.range,
+            Stat::AttackSpeed => base.attack_speed,
+            Stat::Armor => base.armor,
+            Stat::MagicResist => base.magic_resist,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ModifierValue {
+    Flat(f32),
+    Percent(f32), // of the stat after flat modifiers, ex: -0.3 is 30% less
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Modifier {
+    pub stat: Stat,
+    pub value: ModifierValue,
+    pub source: CharacterID,
+    pub expiry: Option<WorldTick>, // first tick the modifier no longer applies, None lasts forever
+}
+
+impl Modifier {
+    pub fn active(&self, tick: WorldTick) -> bool {
+        self.expiry.is_none_or(|expiry| tick < expiry)
+    }
+
+    // order used when several modifiers are added in the same tick
+    fn sort_key(&self) -> (CharacterID, Stat, Option<WorldTick>, u32) {
+        let bits = match self.value {
+            ModifierValue::Flat(value) => value.to_bits(),
+            ModifierValue::Percent(value) => value.to_bits(),
+        };
+        (self.source, self.stat, self.expiry, bits)
+    }
+}
+
+// every modifier stacks, including several from the same source
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Modifiers {
+    pub modifiers: Vec<Modifier>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum ModifiersUpdate {
+    New,
+    Add(Modifier),
+    Remove(Modifier), // remove one modifier equal to this one, ex: from an item that was sold
+    Expire(WorldTick), // remove everything that isn't active at this tick
+}
+
+impl Component for Modifiers {
+    fn update(&self, update: &ComponentUpdateData) -> Self {
+        let mut next = self.clone();
+        if let ComponentUpdateData::Modifiers(update) = update {
+            match update {
+                ModifiersUpdate::New => next = Modifiers::default(),
+                ModifiersUpdate::Add(modifier) => next.modifiers.push(modifier.clone()),
+                ModifiersUpdate::Remove(modifier) => if let Some(i) = next.modifiers.iter().position(|m| m == modifier) {
+                    next.modifiers.remove(i);
+                },
+                ModifiersUpdate::Expire(tick) => next.modifiers.retain(|modifier| modifier.active(*tick)),
+            }
+        }
+        next
+    }
+}
+
+impl GetComponentID for Modifiers {
+    const ID: ComponentID = ComponentID::Modifiers;
+}
+
+pub fn make_modifier_update(cid: &CharacterID, modifier: Modifier) -> Update {
+    Update::Comp(ComponentUpdate {
+        cid: *cid,
+        data: ComponentUpdateData::Modifiers(ModifiersUpdate::Add(modifier)),
+    })
+}
+
+// a stat after leveling and every active modifier, which is what the systems should use instead of the base
+// flat modifiers are added first, then the percentages are added together and applied once
+pub fn effective_stat(world: &World, cid: &CharacterID, stat: Stat) -> Result<f32, WorldError> {
+    let base = stat.base_value(world.base.get_component(cid)?) + level_growth(world, cid, stat);
+    let modifiers = match world.modifiers.get_component(cid) {
+        Ok(modifiers) => modifiers,
+        Err(_) => return Ok(base),
+    };
+    let (flat, percent) = modifiers.modifiers.iter()
+        .filter(|modifier| modifier.stat == stat && modifier.active(world.tick))
+        .fold((0.0, 0.0), |(flat, percent), modifier| match modifier.value {
+            ModifierValue::Flat(value) => (flat + value, percent),
+            ModifierValue::Percent(value) => (flat, percent + value),
+        });
+    Ok(f32::max((base + flat) * f32::max(1.0 + percent, 0.0), 0.0))
+}
+
+pub struct ModifiersSystem;
+
+impl WorldSystem for ModifiersSystem {
+    fn init_world_info(&self) -> Result<WorldInfo, WorldError> {
+        Ok(WorldInfo::new())
+    }
+}
+
+impl ComponentSystem for ModifiersSystem {
+    fn get_component_id(&self) -> ComponentID {
+        ComponentID::Modifiers
+    }
+
+    fn validate_character_command(&self, _: &World, _: &CharacterID, _: &CharacterCommand) -> Result<CharacterCommandState, WorldError> {
+        Err(WorldErrorI::InvalidCommandMapping.err())
+    }
+
+    // drop modifiers that run out before the next tick
+    fn update_character(&self, world: &World, _: &[WorldCommand], cid: &CharacterID, _: f32) -> Result<Vec<Update>, WorldError> {
+        let next_tick = world.tick + 1;
+        let modifiers = world.modifiers.get_component(cid)?;
+        if modifiers.modifiers.iter().all(|modifier| modifier.active(next_tick)) {
+            return Ok(vec![]);
+        }
+        Ok(vec![Update::Comp(ComponentUpdate {
+            cid: *cid,
+            data: ComponentUpdateData::Modifiers(ModifiersUpdate::Expire(next_tick)),
+        })])
+    }
+
+    fn reduce_changes(&self, cid: &CharacterID, world: &World, changes: &[ComponentUpdateData]) -> Result<Vec<ComponentUpdateData>, WorldError> {
+        use ComponentUpdateData::Modifiers as CMod;
+        if !world.characters.contains(cid) {
+            let new_changes: Vec<ComponentUpdateData> = changes.iter()
+                .filter(|new| matches!(*new, CMod(ModifiersUpdate::New)))
+                .cloned().collect();
+            if new_changes.is_empty() {
+                return Err(WorldErrorI::InvalidReduceMapping(*cid, ComponentID::Modifiers).err())
+            } else if new_changes.len() > 1 {
+                return Err(WorldErrorI::MultipleUpdateOverrides(*cid, ComponentID::Modifiers).err())
+            } else {
+                return Ok(new_changes)
+            }
+        }
+        // expire and remove before adding, so that new modifiers are kept even if they expire immediately
+        // after, and sort additions so every world stores them in the same order
+        let expire = changes.iter()
+            .filter_map(|change| match change {
+                CMod(ModifiersUpdate::Expire(tick)) => Some(*tick),
+                _ => None,
+            })
+            .max();
+        let mut added: Vec<Modifier> = changes.iter()
+            .filter_map(|change| match change {
+                CMod(ModifiersUpdate::Add(modifier)) => Some(modifier.clone()),
+                _ => None,
+            })
+            .collect();
+        added.sort_by_key(|modifier| modifier.sort_key());
+        let mut removed: Vec<Modifier> = changes.iter()
+            .filter_map(|change| match change {
+                CMod(ModifiersUpdate::Remove(modifier)) => Some(modifier.clone()),
+                _ => None,
+            })
+            .collect();
+        removed.sort_by_key(|modifier| modifier.sort_key());
+        Ok(expire.map(|tick| CMod(ModifiersUpdate::Expire(tick))).into_iter()
+            .chain(removed.into_iter().map(|modifier| CMod(ModifiersUpdate::Remove(modifier))))
+            .chain(added.into_iter().map(|modifier| CMod(ModifiersUpdate::Add(modifier))))
+            .collect())
+    }
+}
@@ -1,13 +1,16 @@
 
 use nalgebra::{Vector2, Vector3};
 use serde::{Serialize, Deserialize};
-use crate::model::{world::{character::CharacterID, commands::{CharacterCommand, Priority, WorldCommand}, World, WorldError, component::{ComponentID, GetComponentID, ComponentStorageContainer, ComponentUpdateData, Component, ComponentUpdate}, WorldSystem, WorldInfo, ComponentSystem, Update, system::status::{StatusUpdate, StatusPrio, StatusID, Status}, CharacterCommandState, WorldErrorI}, commands::GetCommandID, util::{ItClosest, GroundPos, ItClosestRef}};
+use crate::model::{world::{character::CharacterID, commands::{CharacterCommand, Priority, WorldCommand}, World, WorldError, component::{ComponentID, GetComponentID, ComponentStorageContainer, ComponentUpdateData, Component, ComponentUpdate}, WorldSystem, WorldInfo, ComponentSystem, Update, system::status::{StatusUpdate, StatusPrio, StatusID, Status, is_rooted}, CharacterCommandState, WorldErrorI}, commands::GetCommandID, util::{ItClosest, GroundPos, ItClosestRef}};
 
-use super::base::{CharacterFlip, make_flip_update, make_move_update};
+use super::{base::{CharacterFlip, make_flip_update, make_move_update}, pathfinding::{character_path, character_path_valid}, collision::ground_speed, modifiers::{effective_stat, Stat}};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Movement {
-    pub destination: Option<Vector2<f32>>
+    pub destination: Option<Vector2<f32>>,
+    // remaining waypoints, the last one being where the walk ends
+    // without a destination, the path path_to is following to its target, kept so it isn't planned every tick
+    pub path: Vec<Vector2<f32>>,
 }
 
 impl Component for Movement {
@@ -23,12 +26,13 @@ impl GetComponentID for Movement {
     const ID: ComponentID = ComponentID::Movement;
 }
 
-pub fn make_movement_component_update(cid: CharacterID, dest: Option<Vector2<f32>>) -> Update {
+pub fn make_movement_component_update(cid: CharacterID, dest: Option<Vector2<f32>>, path: Vec<Vector2<f32>>) -> Update {
     Update::Comp(ComponentUpdate {
         cid,
         data: ComponentUpdateData::Movement(
             Movement {
-                destination: dest
+                destination: dest,
+                path,
             }
         )
     })
@@ -90,7 +94,7 @@ const OVERRIDE_STATUS: Status = Status {
 // time for walking
 pub fn walk_to(world: &World, cid: &CharacterID, dest: &Vector2<f32>, range: f32, delta_time: f32) -> Result<(bool, Vec<Update>), WorldError> {
     let base = world.base.get_component(cid)?;
-    let speed = base.speed;
+    let speed = effective_stat(world, cid, Stat::Speed)? * ground_speed(world, cid);
     let max_travel = speed * delta_time;
     let pos = Vector2::new(base.position.x, base.position.y);
     let dir = dest - pos;
@@ -98,7 +102,7 @@ pub fn walk_to(world: &World, cid: &CharacterID, dest: &Vector2<f32>, range: f32
         return Ok((true, vec![]))
     }
     let dist = dir.magnitude();
-    if speed <= 0.0 {
+    if speed <= 0.0 || is_rooted(world, cid) {
         return Ok((dist <= range, vec![]))
     }
     let flip = CharacterFlip::from_dir(&Vector2::new(dir.x, dir.y)).unwrap_or(base.flip);
@@ -121,6 +125,39 @@ pub fn walk_to(world: &World, cid: &CharacterID, dest: &Vector2<f32>, range: f32
     }
 }
 
+// walk_to, but going around terrain when the destination can't be walked to in a straight line
+pub fn path_to(world: &World, cid: &CharacterID, dest: &Vector2<f32>, range: f32, delta_time: f32) -> Result<(bool, Vec<Update>), WorldError> {
+    let pos = world.base.get_component(cid)?.position.ground_pos();
+    // characters that can't move don't need a path
+    let movement = match world.movement.get_component(cid) {
+        Ok(movement) => movement,
+        Err(_) => return walk_to(world, cid, dest, range, delta_time),
+    };
+    // a walk owns the path while it has a destination
+    let cached = movement.destination.is_none();
+    let path = if (dest - pos).magnitude() <= range {
+        vec![]
+    } else if cached && character_path_valid(world, cid, &movement.path, dest) {
+        // the target may have moved within the cell the path ends in
+        movement.path[..movement.path.len() - 1].iter().copied().chain(std::iter::once(*dest)).collect()
+    } else {
+        character_path(world, cid, dest)
+    };
+    let (arrived, path, updates) = match path.split_first() {
+        Some((waypoint, rest)) if !rest.is_empty() => {
+            let (reached, updates) = walk_to(world, cid, waypoint, 0.0, delta_time)?;
+            (false, if reached { rest.to_vec() } else { path }, updates)
+        },
+        _ => {
+            let (arrived, updates) = walk_to(world, cid, dest, range, delta_time)?;
+            (arrived, vec![], updates)
+        },
+    };
+    let cache_update = (cached && path != movement.path)
+        .then(|| make_movement_component_update(*cid, None, path));
+    Ok((arrived, updates.into_iter().chain(cache_update).collect()))
+}
+
 pub struct MovementSystem;
 
 impl WorldSystem for MovementSystem {
@@ -173,13 +210,21 @@ impl ComponentSystem for MovementSystem {
         use ComponentUpdateData::Status as CStatus;
         use StatusUpdate::{Cancel, Try};
         let pos = world.base.get_component(cid)?.position;
-        let dest = world.movement.get_component(cid)?.destination;
+        let movement = world.movement.get_component(cid)?;
+        let dest = movement.destination;
         let status = &world.status.get_component(cid)?.current;
-        let (arrived, position_updates) = match (status.id, dest) {
-            (StatusID::Walk, Some(dest)) => {
-                walk_to(world, cid, &dest, 0.0, delta_time)?
+        // walk towards the next waypoint, only arriving once the last one is reached
+        let (arrived, reached_waypoint, position_updates) = match (status.id, dest, movement.path.split_first()) {
+            (StatusID::Walk, Some(_), Some((waypoint, rest))) if !rest.is_empty() => {
+                let (reached, updates) = walk_to(world, cid, waypoint, 0.0, delta_time)?;
+                (false, reached, updates)
             },
-            _ => (false, vec![])
+            (StatusID::Walk, Some(dest), path) => {
+                let end = path.map(|(end, _)| *end).unwrap_or(dest);
+                let (arrived, updates) = walk_to(world, cid, &end, 0.0, delta_time)?;
+                (arrived, false, updates)
+            },
+            _ => (false, false, vec![])
         };
         let movement_command = commands.iter()
             .filter_map(|cmd| match *cmd {
@@ -191,9 +236,11 @@ impl ComponentSystem for MovementSystem {
             })
             .closest_to(&pos.ground_pos());
         let movement_updates = if let Some(dest) = movement_command {
-            vec![make_movement_component_update(*cid, Some(dest))]
+            vec![make_movement_component_update(*cid, Some(dest), character_path(world, cid, &dest))]
         } else if dest.is_some() && (arrived || status.id != StatusID::Walk) {
-            vec![make_movement_component_update(*cid, None)]
+            vec![make_movement_component_update(*cid, None, vec![])]
+        } else if reached_waypoint {
+            vec![make_movement_component_update(*cid, dest, movement.path[1..].to_vec())]
         } else {
             vec![]
         };
@@ -236,7 +283,7 @@ impl ComponentSystem for MovementSystem {
                         *cid,
                         "Cannot move nonexistent character".to_string()).err())
                 }
-                if !OVERRIDE_STATUS.can_override(&world.status.get_component(cid)?.current) {
+                if !OVERRIDE_STATUS.can_override(&world.status.get_component(cid)?.current) || is_rooted(world, cid) {
                     return Ok(CharacterCommandState::Queued)
                 }
                 Ok(CharacterCommandState::Ready)
@@ -278,22 +325,28 @@ impl ComponentSystem for MovementSystem {
             let pos3 = world.base.get_component(cid)?.position;
             Vector2::new(pos3.x, pos3.y)
         };
-        // if a None is passed in, return None
+        // if a None is passed in without a path, return None
         // else if a dest is passed in, return the closest
-        // else return nothing
-        if changes.iter().any(|change| matches!(*change, CUD_M(Movement { destination: None }))) {
-            return Ok(vec![CUD_M(Movement { destination: None })]);
+        // else return a path from path_to, if any
+        if changes.iter().any(|change| matches!(change, CUD_M(Movement { destination: None, path }) if path.is_empty())) {
+            return Ok(vec![CUD_M(Movement { destination: None, path: vec![] })]);
         }
-        Ok(changes
+        let movements: Vec<&Movement> = changes
             .iter()
             .filter_map(|change| match change {
-                CUD_M(Movement { destination: Some(dest) }) => Some(dest),
+                CUD_M(movement) => Some(movement),
                 _ => None,
             })
-            // choose a destination deterministically
+            .collect();
+        // choose a destination deterministically
+        Ok(movements
+            .iter()
+            .filter_map(|movement| movement.destination.as_ref())
             .closest_to(&pos)
+            .and_then(|dest| movements.iter().find(|movement| movement.destination.as_ref() == Some(dest)))
+            .or_else(|| movements.first())
+            .map(|movement| CUD_M((*movement).clone()))
             .into_iter()
-            .map(|dest| CUD_M(Movement { destination: Some(*dest) }))
             .collect())
     }
 }
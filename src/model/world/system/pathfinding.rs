@@ -0,0 +1,262 @@
+use std::{collections::{BinaryHeap, VecDeque}, cmp::Reverse};
+use nalgebra::Vector2;
+use serde::{Serialize, Deserialize};
+
+use crate::model::world::{World, character::CharacterID, component::ComponentStorageContainer};
+use super::collision::{CollisionInfo, Layer};
+
+// size of a navigation cell in terrain pixels
+const NAV_CELL_PIXELS: i32 = 8;
+
+// integer step costs keep the search exactly the same on every machine
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+// coarse grid of cells that contain terrain, searched by A*
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct NavGrid {
+    pub size: Vector2<i32>,
+    pub cell_size: Vector2<f32>,
+    blocked: Vec<bool>,
+    clearance: Vec<i32>, // cells to the closest blocked cell or the edge of the map
+    world_size: f32,
+}
+
+impl NavGrid {
+    pub fn new(info: &CollisionInfo) -> Self {
+        let size = Vector2::new(
+            (info.size.x + NAV_CELL_PIXELS - 1) / NAV_CELL_PIXELS,
+            (info.size.y + NAV_CELL_PIXELS - 1) / NAV_CELL_PIXELS
+        );
+        let mut blocked = vec![false; (size.x * size.y) as usize];
+        for pixel in &info.terrain {
+            let cell = pixel / NAV_CELL_PIXELS;
+            blocked[(cell.y * size.x + cell.x) as usize] = true;
+        }
+        // impassable ground, checked at the center of each cell
+        let cell_size = Vector2::new(info.world_size / size.x as f32, info.world_size / size.y as f32);
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let center = Vector2::new((x as f32 + 0.5) * cell_size.x, (y as f32 + 0.5) * cell_size.y)
+                    - Vector2::new(info.world_size, info.world_size) / 2.0;
+                if !info.ground.passable(&center) {
+                    blocked[(y * size.x + x) as usize] = true;
+                }
+            }
+        }
+
+        // breadth first search out from every blocked cell, so that walkable is a single lookup
+        let mut clearance = vec![i32::MAX; blocked.len()];
+        let mut queue = VecDeque::new();
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let i = (y * size.x + x) as usize;
+                clearance[i] = match blocked[i] {
+                    true => 0,
+                    false => (x + 1).min(y + 1).min(size.x - x).min(size.y - y),
+                };
+                queue.push_back(Vector2::new(x, y));
+            }
+        }
+        while let Some(cell) = queue.pop_front() {
+            let next_clearance = clearance[(cell.y * size.x + cell.x) as usize] + 1;
+            for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)] {
+                let next = Vector2::new(cell.x + dx, cell.y + dy);
+                if next.x < 0 || next.y < 0 || next.x >= size.x || next.y >= size.y {
+                    continue;
+                }
+                let i = (next.y * size.x + next.x) as usize;
+                if next_clearance < clearance[i] {
+                    clearance[i] = next_clearance;
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        Self {
+            size,
+            cell_size,
+            blocked,
+            clearance,
+            world_size: info.world_size,
+        }
+    }
+
+    fn in_bounds(&self, cell: &Vector2<i32>) -> bool {
+        cell.x >= 0 && cell.y >= 0 && cell.x < self.size.x && cell.y < self.size.y
+    }
+
+    fn is_blocked(&self, cell: &Vector2<i32>) -> bool {
+        !self.in_bounds(cell) || self.blocked[(cell.y * self.size.x + cell.x) as usize]
+    }
+
+    pub fn to_cell(&self, pos: &Vector2<f32>) -> Vector2<i32> {
+        Vector2::new(
+            ((pos.x + self.world_size / 2.0) / self.cell_size.x).floor() as i32,
+            ((pos.y + self.world_size / 2.0) / self.cell_size.y).floor() as i32,
+        )
+    }
+
+    pub fn cell_center(&self, cell: &Vector2<i32>) -> Vector2<f32> {
+        Vector2::new(
+            (cell.x as f32 + 0.5) * self.cell_size.x - self.world_size / 2.0,
+            (cell.y as f32 + 0.5) * self.cell_size.y - self.world_size / 2.0,
+        )
+    }
+
+    // how many cells around a cell must be free of terrain for a collider to stand in it
+    fn reach(&self, radius: f32) -> i32 {
+        (radius / f32::min(self.cell_size.x, self.cell_size.y)).ceil() as i32
+    }
+
+    // a cell can be walked through if no terrain is within the collider's radius of it
+    pub fn walkable(&self, cell: &Vector2<i32>, radius: f32) -> bool {
+        self.in_bounds(cell) && self.clearance[(cell.y * self.size.x + cell.x) as usize] > self.reach(radius)
+    }
+
+    // like walkable, but characters that slid up against a wall may leave through the cells around
+    // where they started, as long as those cells have no terrain in them
+    fn passable(&self, cell: &Vector2<i32>, start: &Vector2<i32>, radius: f32) -> bool {
+        let reach = self.reach(radius);
+        let near_start = (cell.x - start.x).abs() <= reach && (cell.y - start.y).abs() <= reach;
+        self.walkable(cell, radius) || near_start && !self.is_blocked(cell)
+    }
+
+    // check the straight line between two positions, in steps of half a cell
+    pub fn line_of_sight(&self, from: &Vector2<f32>, to: &Vector2<f32>, radius: f32) -> bool {
+        let start = self.to_cell(from);
+        let diff = to - from;
+        let step = f32::min(self.cell_size.x, self.cell_size.y) / 2.0;
+        let steps = i32::max(1, (diff.magnitude() / step).ceil() as i32);
+        (0..=steps).all(|k| self.passable(&self.to_cell(&(from + diff * (k as f32 / steps as f32))), &start, radius))
+    }
+
+    fn heuristic(a: &Vector2<i32>, b: &Vector2<i32>) -> u32 {
+        let (dx, dy) = ((a.x - b.x).unsigned_abs(), (a.y - b.y).unsigned_abs());
+        STRAIGHT_COST * dx.max(dy) + (DIAGONAL_COST - STRAIGHT_COST) * dx.min(dy)
+    }
+
+    // A* from one cell to another. if the goal can't be reached, the path ends at the reached cell
+    // closest to the goal
+    fn search(&self, start: Vector2<i32>, goal: Vector2<i32>, radius: f32) -> Vec<Vector2<i32>> {
+        let index = |cell: &Vector2<i32>| cell.y * self.size.x + cell.x;
+        let mut open = BinaryHeap::new();
+        let mut came_from: Vec<Option<Vector2<i32>>> = vec![None; self.blocked.len()];
+        let mut cost = vec![u32::MAX; self.blocked.len()];
+        let mut closest = (Self::heuristic(&start, &goal), start);
+        if !self.in_bounds(&start) {
+            return vec![start];
+        }
+        cost[index(&start) as usize] = 0;
+        // ties are broken by heuristic then cell index so the search is deterministic
+        open.push(Reverse((Self::heuristic(&start, &goal), Self::heuristic(&start, &goal), index(&start), start.x, start.y)));
+        while let Some(Reverse((f, h, _, x, y))) = open.pop() {
+            let cell = Vector2::new(x, y);
+            let current_cost = cost[index(&cell) as usize];
+            // skip entries that a cheaper path to the same cell replaced
+            if f > current_cost + h {
+                continue;
+            }
+            if cell == goal {
+                closest = (0, cell);
+                break;
+            }
+            if (h, index(&cell)) < (closest.0, index(&closest.1)) {
+                closest = (h, cell);
+            }
+            for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)] {
+                let next = Vector2::new(x + dx, y + dy);
+                if !self.passable(&next, &start, radius) {
+                    continue;
+                }
+                let diagonal = dx != 0 && dy != 0;
+                // don't cut corners
+                if diagonal && !(self.passable(&Vector2::new(x + dx, y), &start, radius) && self.passable(&Vector2::new(x, y + dy), &start, radius)) {
+                    continue;
+                }
+                let next_cost = current_cost + if diagonal { DIAGONAL_COST } else { STRAIGHT_COST };
+                if next_cost < cost[index(&next) as usize] {
+                    cost[index(&next) as usize] = next_cost;
+                    came_from[index(&next) as usize] = Some(cell);
+                    let h = Self::heuristic(&next, &goal);
+                    open.push(Reverse((next_cost + h, h, index(&next), next.x, next.y)));
+                }
+            }
+        }
+        let mut path = vec![closest.1];
+        while let Some(prev) = came_from[index(path.last().unwrap()) as usize] {
+            path.push(prev);
+        }
+        path.reverse();
+        path
+    }
+
+    // find a path of waypoints from one position to another, not including the start
+    // the last waypoint is the destination, or the closest reachable point to it
+    pub fn find_path(&self, from: &Vector2<f32>, to: &Vector2<f32>, radius: f32) -> Vec<Vector2<f32>> {
+        if self.line_of_sight(from, to, radius) {
+            return vec![*to];
+        }
+        let goal = self.to_cell(to);
+        let cells = self.search(self.to_cell(from), goal, radius);
+        let reached = *cells.last().unwrap_or(&goal) == goal;
+        let mut points: Vec<Vector2<f32>> = cells.iter().skip(1).map(|cell| self.cell_center(cell)).collect();
+        match points.last_mut() {
+            Some(last) if reached => *last = *to,
+            None if reached => points.push(*to),
+            _ => (),
+        }
+        self.smooth(from, points, radius)
+    }
+
+    // string pulling: skip every waypoint that can be seen past
+    fn smooth(&self, from: &Vector2<f32>, points: Vec<Vector2<f32>>, radius: f32) -> Vec<Vector2<f32>> {
+        let mut smoothed = vec![];
+        let mut current = *from;
+        let mut i = 0;
+        while i < points.len() {
+            let mut furthest = i;
+            for j in (i + 1..points.len()).rev() {
+                if self.line_of_sight(&current, &points[j], radius) {
+                    furthest = j;
+                    break;
+                }
+            }
+            current = points[furthest];
+            smoothed.push(current);
+            i = furthest + 1;
+        }
+        smoothed
+    }
+}
+
+// the grid a character navigates, where it stands and its collider radius
+// None for characters that don't collide with terrain
+fn character_nav<'a>(world: &'a World, cid: &CharacterID) -> Option<(&'a NavGrid, Vector2<f32>, f32)> {
+    match (&world.info.terrain, world.collision.get_component(cid), world.base.get_component(cid)) {
+        (Some(terrain), Ok(collision), Ok(base)) if collision.against.contains(&Layer::Terrain) =>
+            Some((&terrain.nav, Vector2::new(base.position.x, base.position.y), collision.collider.radius())),
+        _ => None,
+    }
+}
+
+// path for a character from where it stands, using its collider
+// characters that don't collide with terrain walk straight there
+pub fn character_path(world: &World, cid: &CharacterID, dest: &Vector2<f32>) -> Vec<Vector2<f32>> {
+    match character_nav(world, cid) {
+        Some((nav, from, radius)) => nav.find_path(&from, dest, radius),
+        None => vec![*dest],
+    }
+}
+
+// whether a path from character_path can still be followed towards dest without planning again
+// it can't once dest has moved to another cell than the one the path ends in, or the next
+// waypoint can't be walked to in a straight line anymore
+pub fn character_path_valid(world: &World, cid: &CharacterID, path: &[Vector2<f32>], dest: &Vector2<f32>) -> bool {
+    match (character_nav(world, cid), path.first(), path.last()) {
+        (Some((nav, from, radius)), Some(next), Some(end)) =>
+            nav.to_cell(end) == nav.to_cell(dest) && nav.line_of_sight(&from, next, radius),
+        (None, Some(_), Some(_)) => true,
+        _ => false,
+    }
+}
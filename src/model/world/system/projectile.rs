@@ -1,14 +1,31 @@
+use std::collections::BTreeSet;
+
 use nalgebra::{Vector3, Vector2};
 use serde::{Serialize, Deserialize};
 
-use crate::model::world::{character::{CharacterID, CharacterType}, component::{GetComponentID, ComponentID, ComponentStorageContainer, ComponentUpdateData, Component, ComponentUpdate}, World, WorldError, WorldInfo, WorldSystem, commands::{CharacterCommand, WorldCommand, Priority}, ComponentSystem, Update, WorldUpdate, CharacterCommandState, WorldErrorI};
+use crate::model::world::{character::{CharacterID, CharacterType}, component::{GetComponentID, ComponentID, ComponentStorageContainer, ComponentStorageCommon, ComponentUpdateData, Component, ComponentUpdate}, World, WorldError, WorldInfo, WorldSystem, commands::{CharacterCommand, WorldCommand, Priority}, ComponentSystem, Update, WorldUpdate, CharacterCommandState, WorldErrorI};
 
-use super::{base::{CharacterBase, CharacterFlip, make_move_update, make_flip_update, CharacterBaseUpdate}, health::make_health_update};
+use super::{base::{CharacterBase, CharacterFlip, make_move_update, make_flip_update, CharacterBaseUpdate}, health::{make_damage_updates, mitigated_damage, DamageKind}, death::is_alive, modifiers::{effective_stat, Stat}, collision::{Layer, Collider}, team::{CharacterTeam, CharacterTeamUpdate, get_team, are_allies}};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Skillshot {
+    pub direction: Vector2<f32>, // normalized
+    pub range: f32, // distance left to fly
+    pub radius: f32,
+    pub layers: BTreeSet<Layer>, // the layers of the characters it can hit
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum ProjectileTarget {
+    Character(CharacterID), // homes onto the character, so it always hits
+    Direction(Skillshot), // flies straight and hits the first character in the way, or stops at terrain
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Projectile {
     pub origin: CharacterID,
-    pub target: CharacterID,
+    pub target: ProjectileTarget,
+    pub kind: DamageKind,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -29,17 +46,18 @@ impl GetComponentID for Projectile {
 
 impl Default for Projectile {
     fn default() -> Self {
-        Self { origin: CharacterID::error(), target: CharacterID::error() }
+        Self { origin: CharacterID::error(), target: ProjectileTarget::Character(CharacterID::error()), kind: DamageKind::Physical }
     }
 }
 
 pub struct ProjectileCreationInfo {
     pub proj_id: CharacterID,
     pub origin: CharacterID,
-    pub target: CharacterID,
+    pub target: ProjectileTarget,
     pub starting_offset: Vector3<f32>,
     pub speed: f32,
     pub damage: f32,
+    pub kind: DamageKind,
 }
 
 pub fn create(
@@ -47,6 +65,19 @@ pub fn create(
     info: &ProjectileCreationInfo
 ) -> Result<Vec<Update>, WorldError> {
     let typ = CharacterType::Projectile;
+    if let ProjectileTarget::Direction(skillshot) = &info.target {
+        let dir = skillshot.direction;
+        if !dir.x.is_finite() || !dir.y.is_finite() || dir.x == 0.0 && dir.y == 0.0 || !skillshot.range.is_finite() || !skillshot.radius.is_finite() {
+            return Err(WorldErrorI::InvalidCommandData(info.origin, "Skillshot needs a direction and range".to_string()).err());
+        }
+    }
+    let target = match &info.target {
+        ProjectileTarget::Direction(skillshot) => ProjectileTarget::Direction(Skillshot {
+            direction: skillshot.direction.normalize(),
+            ..skillshot.clone()
+        }),
+        target => target.clone(),
+    };
     let position = {
         let base = world.base.get_component(&info.origin)?;
         base.position + Vector3::new(info.starting_offset.x * base.flip.dir(), info.starting_offset.y, info.starting_offset.z)
@@ -65,6 +96,8 @@ pub fn create(
                         attack_damage: info.damage,
                         range: 0.0,
                         attack_speed: 0.0,
+                        armor: 0.0,
+                        magic_resist: 0.0,
                         flip: CharacterFlip::Right,
                         targetable: false,
                     }
@@ -77,10 +110,18 @@ pub fn create(
                 ProjectileUpdate(
                     Projectile {
                         origin: info.origin,
-                        target: info.target
+                        target,
+                        kind: info.kind,
                     }
                 )
             )
+        }),
+        // projectiles fight for the team of whoever fired them
+        Update::Comp(ComponentUpdate {
+            cid: info.proj_id,
+            data: ComponentUpdateData::Team(CharacterTeamUpdate(CharacterTeam {
+                team: get_team(world, &info.origin),
+            }))
         })
     ])
 }
@@ -98,6 +139,8 @@ impl WorldSystem for ProjectileSystem {
                 attack_damage: 0.0,
                 range: 0.0,
                 attack_speed: 0.0,
+                armor: 0.0,
+                magic_resist: 0.0,
                 flip: CharacterFlip::Right,
                 targetable: false,
                 speed: 0.0,
@@ -113,7 +156,7 @@ impl WorldSystem for ProjectileSystem {
 // time for walking
 pub fn fly_to(world: &World, cid: &CharacterID, dest: &Vector3<f32>, range: f32, delta_time: f32) -> Result<(bool, Vec<Update>), WorldError> {
     let base = world.base.get_component(cid)?;
-    let speed = base.speed;
+    let speed = effective_stat(world, cid, Stat::Speed)?;
     let max_travel = speed * delta_time;
     let dir = dest - base.position;
     if dir.x == 0.0 && dir.y == 0.0 && dir.z == 0.0 {
@@ -143,39 +186,122 @@ pub fn fly_to(world: &World, cid: &CharacterID, dest: &Vector3<f32>, range: f32,
     }
 }
 
+// remove the projectile and damage what it hit
+fn projectile_hit(world: &World, cid: &CharacterID, origin: &CharacterID, target: &CharacterID, kind: DamageKind, damage: f32) -> Result<Vec<Update>, WorldError> {
+    if are_allies(world, cid, target) {
+        return Ok(vec![Update::World(WorldUpdate::RemoveCharacterID(*cid))]);
+    }
+    let health = world.health.get_component(target)?;
+    // the death system handles characters that can die, anything else is just removed
+    let can_die = world.death.get_component(target).is_ok();
+    let damage_updates = make_damage_updates(world, origin, target, kind, damage)?;
+    let remove_target = !can_die && health.health - mitigated_damage(world, target, kind, damage)? <= 0.0;
+    Ok([Update::World(WorldUpdate::RemoveCharacterID(*cid))].into_iter()
+       .chain(damage_updates)
+       .chain(remove_target.then_some(Update::World(WorldUpdate::RemoveCharacterID(*target))))
+       .collect())
+}
+
+// the first character a skillshot touches while moving from a position, and how far along the
+// movement it touches it
+fn skillshot_first_hit(world: &World, cid: &CharacterID, origin: &CharacterID, skillshot: &Skillshot, from: &Vector2<f32>, mov: &Vector2<f32>) -> Option<(f32, CharacterID)> {
+    let length = mov.magnitude();
+    let mut others = world.collision.get_characters();
+    others.sort();
+    others.into_iter()
+        // skillshots fly through allies
+        .filter(|other| *other != *cid && *other != *origin && is_alive(world, other) && !are_allies(world, cid, other))
+        .filter_map(|other| {
+            let collision = world.collision.get_component(&other).ok()?;
+            if !collision.layer.map(|layer| skillshot.layers.contains(&layer)).unwrap_or(false) {
+                return None;
+            }
+            let base = world.base.get_component(&other).ok()?;
+            if !base.targetable {
+                return None;
+            }
+            let reach = skillshot.radius + collision.collider.radius();
+            let diff = Vector2::new(base.position.x, base.position.y) - from;
+            if length <= 0.0 {
+                return (diff.magnitude() <= reach).then_some((0.0, other));
+            }
+            // where along the movement the circles first touch
+            let dir = mov / length;
+            let along = diff.dot(&dir);
+            let across_sq = diff.magnitude_squared() - along * along;
+            if across_sq > reach * reach {
+                return None;
+            }
+            let touch = f32::max(along - f32::sqrt(reach * reach - across_sq), 0.0);
+            (touch <= length && along + reach >= 0.0).then_some((touch, other))
+        })
+        // closest first, then lowest id so every world agrees
+        .min_by(|(a_dist, a), (b_dist, b)| a_dist.total_cmp(b_dist).then(a.cmp(b)))
+}
+
+// fly straight for the rest of the range, hitting the first character in the way
+fn skillshot_update(world: &World, cid: &CharacterID, origin: &CharacterID, kind: DamageKind, skillshot: &Skillshot, delta_time: f32) -> Result<Vec<Update>, WorldError> {
+    let base = world.base.get_component(cid)?;
+    let speed = effective_stat(world, cid, Stat::Speed)?;
+    let damage = effective_stat(world, cid, Stat::AttackDamage)?;
+    let from = Vector2::new(base.position.x, base.position.y);
+    let travel = f32::min(f32::max(speed * delta_time, 0.0), f32::max(skillshot.range, 0.0));
+    let mov = skillshot.direction * travel;
+    // projectiles stop at terrain instead of sliding along it
+    let (mov, blocked) = match world.info.terrain.as_ref().and_then(|terrain| terrain.cast(&Collider::Point, &from, &mov)) {
+        Some(free) => (free, true),
+        None => (mov, false),
+    };
+    if let Some((_, target)) = skillshot_first_hit(world, cid, origin, skillshot, &from, &mov) {
+        return projectile_hit(world, cid, origin, &target, kind, damage);
+    }
+    let range = skillshot.range - travel;
+    if blocked || range <= 0.0 {
+        return Ok(vec![Update::World(WorldUpdate::RemoveCharacterID(*cid))]);
+    }
+    let flip = CharacterFlip::from_dir(&skillshot.direction).unwrap_or(base.flip);
+    Ok(vec![
+        make_move_update(*cid, Priority::Walk, Vector3::new(mov.x, mov.y, 0.0)),
+        make_flip_update(*cid, Priority::Walk, flip),
+        Update::Comp(ComponentUpdate {
+            cid: *cid,
+            data: ComponentUpdateData::Projectile(ProjectileUpdate(Projectile {
+                origin: *origin,
+                target: ProjectileTarget::Direction(Skillshot { range, ..skillshot.clone() }),
+                kind,
+            })),
+        }),
+    ])
+}
+
 impl ComponentSystem for ProjectileSystem {
     fn get_component_id(&self) -> ComponentID {
         ComponentID::Projectile
     }
 
     fn update_character(&self, world: &World, _commands: &[WorldCommand], cid: &CharacterID, delta_time: f32) -> Result<Vec<Update>, WorldError> {
-        let target = world.projectile.get_component(cid)?.target;
-        if world.characters.get(&target).is_none() {
+        let Projectile { origin, target, kind } = world.projectile.get_component(cid)?.clone();
+        let target = match target {
+            ProjectileTarget::Character(target) => target,
+            ProjectileTarget::Direction(skillshot) => return skillshot_update(world, cid, &origin, kind, &skillshot, delta_time),
+        };
+        if !is_alive(world, &target) {
             return Ok(vec![Update::World(WorldUpdate::RemoveCharacterID(*cid))]);
             // return Err(WorldError::MissingCharacter(target, "Projectile target doesn't exist".to_string()))
         }
         let base = world.base.get_component(&target)?;
         let dest = base.position + base.center_offset;
         let range = 0.0;
-        let damage = world.base.get_component(cid)?.attack_damage;
+        let damage = effective_stat(world, cid, Stat::AttackDamage)?;
         let (arrived, fly_updates) = fly_to(world, cid, &dest, range, delta_time)?;
         if arrived {
             // world.erase_character(&cid)?;
             // do damage
-            let health = world.health.get_component(&target)?;
             // health.health -= damage;
             // if health.health - damage <= 0.0 {
             //     world.erase_character(&target)?;
             // }
-            Ok([
-               Some(Update::World(WorldUpdate::RemoveCharacterID(*cid))),
-               Some(make_health_update(&target, -damage)),
-               if health.health - damage <= 0.0 {
-                   Some(Update::World(WorldUpdate::RemoveCharacterID(target)))
-               } else {
-                   None
-               }
-            ].into_iter().flatten().collect())
+            projectile_hit(world, cid, &origin, &target, kind, damage)
         } else {
             Ok(fly_updates)
         }
@@ -1,15 +1,22 @@
 use std::collections::HashMap;
 
-use crate::model::world::{ComponentID, ComponentSystem, character::CharacterID, World, WorldError, commands::{CharacterCommand, WorldCommand}, Update, component::{ComponentUpdateData, Component, GetComponentID, ComponentStorageContainer}, WorldSystem, WorldInfo, CharacterCommandState, WorldErrorI};
+use crate::model::{world::{ComponentID, ComponentSystem, character::CharacterID, World, WorldError, commands::{CharacterCommand, WorldCommand, Priority}, Update, component::{ComponentUpdateData, Component, GetComponentID, ComponentStorageContainer, ComponentUpdate}, WorldSystem, WorldInfo, CharacterCommandState, WorldErrorI}, WorldTick, TICK_RATE};
 use itertools::Itertools;
+use nalgebra::Vector3;
 use serde::{Serialize, Deserialize};
 
+use super::{base::make_move_update, modifiers::{make_modifier_update, Modifier, ModifierValue, Stat}};
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy)]
 pub enum StatusID {
     Idle,
     Walk,
     AutoAttack,
     Flash,
+    Dead,
+    Stunned,
+    Aoe,
+    Bolt,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Copy)]
@@ -21,6 +28,7 @@ pub enum StatusPrio {
     AbilityOverride,
     AbilityPrioritized,
     Stunned,
+    Dead,
 }
 
 impl StatusPrio {
@@ -34,6 +42,7 @@ impl StatusPrio {
             AbilityOverride => 3,
             AbilityPrioritized => 4,
             Stunned => 5,
+            Dead => 6,
         }
     }
 }
@@ -53,13 +62,46 @@ impl Status {
 
 impl Default for StatusComponent {
     fn default() -> Self {
-        Self { current: idle_status() }
+        Self { current: idle_status(), effects: vec![] }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum CrowdControl {
+    Stun, // blocks movement and abilities, and takes over the current status
+    Root, // blocks movement
+    Silence, // blocks abilities
+    Slow(f32), // fraction of speed taken away, ex: 0.3 leaves 70% speed
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StatusEffect {
+    pub cc: CrowdControl,
+    pub source: CharacterID,
+    pub expiry: WorldTick, // first tick the effect no longer applies
+}
+
+impl StatusEffect {
+    pub fn active(&self, tick: WorldTick) -> bool {
+        tick < self.expiry
+    }
+
+    // order used when several effects are applied in the same tick
+    fn sort_key(&self) -> (CharacterID, WorldTick, u8, u32) {
+        let (kind, amount) = match self.cc {
+            CrowdControl::Stun => (0, 0.0),
+            CrowdControl::Root => (1, 0.0),
+            CrowdControl::Silence => (2, 0.0),
+            CrowdControl::Slow(amount) => (3, amount),
+        };
+        (self.source, self.expiry, kind, f32::to_bits(amount))
     }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct StatusComponent {
     pub current: Status,
+    pub effects: Vec<StatusEffect>, // crowd control that lasts for some time, on top of the current status
 }
 
 pub fn idle_status() -> Status {
@@ -78,7 +120,17 @@ impl Component for StatusComponent {
         use ComponentUpdateData::Status;
         use StatusUpdate::*;
         match update.clone() {
-            Status(New(status)) => Self { current: status },
+            Status(New(status)) => Self { current: status, effects: self.effects.clone() },
+            Status(Apply(effect)) => {
+                let mut next = self.clone();
+                next.effects.push(effect);
+                next
+            },
+            Status(Expire(tick)) => Self {
+                current: self.current.clone(),
+                effects: self.effects.iter().filter(|effect| effect.active(tick)).cloned().collect(),
+            },
+            Status(Cleanse) => Self { current: self.current.clone(), effects: vec![] },
             _ => self.clone()
         }
     }
@@ -92,6 +144,58 @@ pub enum StatusUpdate {
     Try(StatusPrio, Status), // the first param is the queueing/overriding priority
     Cancel(StatusID),
     ChangePrio(StatusID, StatusPrio),
+    Apply(StatusEffect),
+    Expire(WorldTick), // remove effects that aren't active at this tick
+    Cleanse, // remove every effect
+}
+
+const STUNNED_STATUS: Status = Status {
+    prio: StatusPrio::Stunned,
+    id: StatusID::Stunned,
+};
+
+fn has_effect<F>(world: &World, cid: &CharacterID, matches: F) -> bool where F: Fn(&CrowdControl) -> bool {
+    world.status.get_component(cid)
+        .map(|status| status.effects.iter().any(|effect| effect.active(world.tick) && matches(&effect.cc)))
+        .unwrap_or(false)
+}
+
+pub fn is_stunned(world: &World, cid: &CharacterID) -> bool {
+    has_effect(world, cid, |cc| *cc == CrowdControl::Stun)
+}
+
+// rooted characters can't walk, but can still cast
+pub fn is_rooted(world: &World, cid: &CharacterID) -> bool {
+    has_effect(world, cid, |cc| matches!(*cc, CrowdControl::Stun | CrowdControl::Root))
+}
+
+// silenced characters can't cast abilities, but can still walk and auto attack
+pub fn is_silenced(world: &World, cid: &CharacterID) -> bool {
+    has_effect(world, cid, |cc| matches!(*cc, CrowdControl::Stun | CrowdControl::Silence))
+}
+
+// apply crowd control for a duration in seconds
+// stuns also try to take over the status, and slows also lower speed until they run out
+pub fn make_crowd_control_updates(world: &World, source: &CharacterID, target: &CharacterID, cc: CrowdControl, duration: f32) -> Vec<Update> {
+    let expiry = world.tick + 1 + f32::ceil(duration * TICK_RATE) as WorldTick;
+    let comp = |data| Update::Comp(ComponentUpdate { cid: *target, data });
+    let effect = comp(ComponentUpdateData::Status(StatusUpdate::Apply(StatusEffect { cc, source: *source, expiry })));
+    match cc {
+        CrowdControl::Stun => vec![
+            effect,
+            comp(ComponentUpdateData::Status(StatusUpdate::Try(STUNNED_STATUS.prio, STUNNED_STATUS))),
+        ],
+        CrowdControl::Slow(amount) => vec![
+            effect,
+            make_modifier_update(target, Modifier {
+                stat: Stat::Speed,
+                value: ModifierValue::Percent(-amount),
+                source: *source,
+                expiry: Some(expiry),
+            }),
+        ],
+        CrowdControl::Root | CrowdControl::Silence => vec![effect],
+    }
 }
 
 pub struct StatusSystem;
@@ -110,8 +214,26 @@ impl ComponentSystem for StatusSystem {
         Err(WorldErrorI::InvalidCommandMapping.err())
     }
 
-    fn update_character(&self, _: &World, _: &[WorldCommand], _: &CharacterID, _: f32) -> Result<Vec<Update>, WorldError> {
-        Ok(vec![])
+    // hold stunned characters in place and end effects that run out before the next tick
+    fn update_character(&self, world: &World, _: &[WorldCommand], cid: &CharacterID, _: f32) -> Result<Vec<Update>, WorldError> {
+        let next_tick = world.tick + 1;
+        let status = world.status.get_component(cid)?;
+        let comp = |data| Update::Comp(ComponentUpdate { cid: *cid, data });
+        let mut updates = vec![];
+        if status.current.id == StatusID::Stunned {
+            // nothing of lower priority can move a stunned character
+            updates.push(make_move_update(*cid, Priority::Stun, Vector3::zeros()));
+        }
+        if status.effects.iter().all(|effect| effect.active(next_tick)) {
+            return Ok(updates);
+        }
+        updates.push(comp(ComponentUpdateData::Status(StatusUpdate::Expire(next_tick))));
+        let stunned_next = status.effects.iter()
+            .any(|effect| effect.cc == CrowdControl::Stun && effect.active(next_tick));
+        if status.current.id == StatusID::Stunned && !stunned_next {
+            updates.push(comp(ComponentUpdateData::Status(StatusUpdate::Cancel(StatusID::Stunned))));
+        }
+        Ok(updates)
     }
 
     fn reduce_changes(&self, cid: &CharacterID, world: &World, changes: &[ComponentUpdateData]) -> Result<Vec<ComponentUpdateData>, WorldError> {
@@ -182,9 +304,28 @@ impl ComponentSystem for StatusSystem {
             .sorted_unstable_by(|(a_prio, a), (b_prio, b)| a_prio.get_prio().cmp(&b_prio.get_prio()).reverse()
                                 .then(a.id.cmp(&b.id)));
         let status: Option<Status> = iter.next().map(|(_, status)| status);
+
+        // effects are expired or cleansed before new ones are applied, and sorted so every world
+        // stores them in the same order
+        let expire = changes.iter()
+            .filter_map(|change| match *change {
+                CStatus(Expire(tick)) => Some(tick),
+                _ => None,
+            })
+            .max();
+        let cleanse = changes.iter().any(|change| matches!(*change, CStatus(Cleanse)));
+        let applied = changes.iter()
+            .filter_map(|change| match change {
+                CStatus(Apply(effect)) => Some(effect.clone()),
+                _ => None,
+            })
+            .sorted_by_key(|effect| effect.sort_key());
         Ok(status
            .into_iter()
            .map(|status| CStatus(New(status))) // New indicates this is supposed to be the only one
+           .chain(expire.map(|tick| CStatus(Expire(tick))))
+           .chain(cleanse.then_some(CStatus(Cleanse)))
+           .chain(applied.map(|effect| CStatus(Apply(effect))))
            .collect())
     }
 }
@@ -0,0 +1,71 @@
+use serde::{Serialize, Deserialize};
+
+use crate::model::{world::{component::{Component, ComponentUpdateData, GetComponentID, ComponentID, ComponentStorageContainer}, WorldSystem, WorldError, WorldInfo, ComponentSystem, commands::{CharacterCommand, WorldCommand}, character::CharacterID, World, Update, CharacterCommandState, WorldErrorI}, player::model::TeamID};
+
+// characters without a team are enemies of everyone
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CharacterTeam {
+    pub team: Option<TeamID>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CharacterTeamUpdate(pub CharacterTeam);
+
+impl Component for CharacterTeam {
+    fn update(&self, update: &ComponentUpdateData) -> Self {
+        match update {
+            ComponentUpdateData::Team(CharacterTeamUpdate(team)) => *team,
+            _ => *self,
+        }
+    }
+}
+
+impl GetComponentID for CharacterTeam {
+    const ID: ComponentID = ComponentID::Team;
+}
+
+pub fn get_team(world: &World, cid: &CharacterID) -> Option<TeamID> {
+    world.team.get_component(cid).ok().and_then(|team| team.team)
+}
+
+// characters are allies when they are both on the same team
+pub fn are_allies(world: &World, a: &CharacterID, b: &CharacterID) -> bool {
+    match (get_team(world, a), get_team(world, b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
+
+pub struct TeamSystem;
+
+impl WorldSystem for TeamSystem {
+    fn init_world_info(&self) -> Result<WorldInfo, WorldError> {
+        Ok(WorldInfo::new())
+    }
+}
+
+impl ComponentSystem for TeamSystem {
+    fn get_component_id(&self) -> ComponentID {
+        ComponentID::Team
+    }
+
+    fn validate_character_command(&self, _: &World, _: &CharacterID, _: &CharacterCommand) -> Result<CharacterCommandState, WorldError> {
+        Err(WorldErrorI::InvalidCommandMapping.err())
+    }
+
+    fn update_character(&self, _: &World, _: &[WorldCommand], _: &CharacterID, _: f32) -> Result<Vec<Update>, WorldError> {
+        Ok(vec![])
+    }
+
+    fn reduce_changes(&self, cid: &CharacterID, _: &World, changes: &[ComponentUpdateData]) -> Result<Vec<ComponentUpdateData>, WorldError> {
+        // team updates replace the whole component, so two in one tick can't be ordered
+        let team_changes: Vec<ComponentUpdateData> = changes.iter()
+            .filter(|change| matches!(change, ComponentUpdateData::Team(_)))
+            .cloned()
+            .collect();
+        if team_changes.len() > 1 {
+            return Err(WorldErrorI::MultipleUpdateOverrides(*cid, ComponentID::Team).err())
+        }
+        Ok(team_changes)
+    }
+}
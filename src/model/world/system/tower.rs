@@ -0,0 +1,48 @@
+use nalgebra::Vector2;
+use serde::{Serialize, Deserialize};
+
+use crate::model::world::{component::{GetComponentID, ComponentID, ComponentUpdateData, Component}, World, character::{CharacterID, CharacterType, create_common_components}, WorldError, WorldInfo, WorldSystem, commands::{CharacterCommand, WorldCommand}, ComponentSystem, Update, CharacterCommandState, WorldErrorI};
+use crate::model::player::model::TeamID;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Tower {
+
+}
+
+impl Component for Tower {
+    fn update(&self, _update: &ComponentUpdateData) -> Self {
+        self.clone()
+    }
+}
+
+impl GetComponentID for Tower {
+    const ID: ComponentID = ComponentID::Tower;
+}
+
+pub struct TowerSystem;
+
+impl WorldSystem for TowerSystem {
+    fn init_world_info(&self) -> Result<WorldInfo, WorldError> {
+        Ok(WorldInfo::new())
+    }
+}
+
+impl ComponentSystem for TowerSystem {
+    fn get_component_id(&self) -> ComponentID {
+        ComponentID::Tower
+    }
+    fn validate_character_command(&self, _: &World, _: &CharacterID, _: &CharacterCommand) -> Result<CharacterCommandState, WorldError> {
+        Err(WorldErrorI::InvalidCommandMapping.err())
+    }
+    fn update_character(&self, _: &World, _: &[WorldCommand], _: &CharacterID, _: f32) -> Result<Vec<Update>, WorldError> {
+        Ok(vec![])
+    }
+    fn reduce_changes(&self, _: &CharacterID, _: &World, changes: &[ComponentUpdateData]) -> Result<Vec<ComponentUpdateData>, WorldError> {
+        Ok(changes.to_vec())
+    }
+}
+
+// towers never move, so they get no movement or flash
+pub fn create(world: &World, id: &CharacterID, position: Vector2<f32>, team: Option<TeamID>) -> Result<Vec<Update>, WorldError> {
+    create_common_components(world, id, CharacterType::Tower, position, team, [ComponentUpdateData::Tower])
+}
@@ -0,0 +1,48 @@
+use std::collections::HashSet;
+
+use nalgebra::Vector2;
+use serde::{Serialize, Deserialize};
+
+use crate::model::player::model::TeamID;
+
+use super::{World, character::CharacterID, system::{collision::Collider, death::is_alive, team::get_team}};
+
+// how far a character type can see
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VisionInfo {
+    pub radius: f32,
+}
+
+// the position and sight radius of every living character on the team that can see
+pub fn team_viewers(world: &World, team: TeamID) -> Vec<(Vector2<f32>, f32)> {
+    world.base.components.iter()
+        .filter(|(cid, _)| get_team(world, cid) == Some(team) && is_alive(world, cid))
+        .filter_map(|(_, base)| world.info.vision.get(&base.ctype)
+            .map(|vision| (Vector2::new(base.position.x, base.position.y), vision.radius)))
+        .collect()
+}
+
+// terrain blocks vision the same way it blocks skillshots
+pub fn line_of_sight(world: &World, from: &Vector2<f32>, to: &Vector2<f32>) -> bool {
+    match &world.info.terrain {
+        Some(terrain) => terrain.cast(&Collider::Point, from, &(to - from)).is_none(),
+        None => true,
+    }
+}
+
+pub fn in_sight(world: &World, viewers: &[(Vector2<f32>, f32)], point: &Vector2<f32>) -> bool {
+    viewers.iter().any(|(position, radius)|
+        (point - position).magnitude_squared() <= radius * radius && line_of_sight(world, position, point))
+}
+
+// every character the team can see, vision is shared between allies
+// allies themselves are always visible, even when dead
+pub fn team_vision(world: &World, team: TeamID) -> HashSet<CharacterID> {
+    let viewers = team_viewers(world, team);
+    world.base.components.iter()
+        .filter(|(cid, _)| world.characters.contains(cid))
+        .filter(|(cid, base)| get_team(world, cid) == Some(team)
+            || in_sight(world, &viewers, &Vector2::new(base.position.x, base.position.y)))
+        .map(|(cid, _)| *cid)
+        .collect()
+}
@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+use itertools::Itertools;
+use nalgebra::Vector2;
+use crate::model::world::{World, character::{CharacterID, CharacterType, CharacterIDGenerator}, commands::{WorldCommand, CharacterCommand}, component::{ComponentID, ComponentStorageContainer, ComponentStorageCommon}, system::{death::is_alive, team::are_allies, movement::MoveCharacter, auto_attack::AutoAttackCommand, modifiers::{effective_stat, Stat}}};
+
+// how close a character has to be to its home to count as being back
+const HOME_RADIUS: f32 = 0.2;
+
+// lane waypoints are looser, since a whole wave walks to the same one
+const WAYPOINT_RADIUS: f32 = 0.5;
+
+// ticks a walk can go without getting any closer before it is given again, ex: after the
+// character was pushed off course or its move was refused
+const STALL_TICKS: u32 = 30;
+
+// how much closer a walk has to get to count as progress
+const STALL_PROGRESS: f32 = 0.05;
+
+// projectile ids given to each auto attack the ai starts
+const ATTACK_ID_COUNT: u64 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AiMode {
+    Idle,
+    Chase(CharacterID),
+    Leash, // walking back home, ignoring enemies until it gets there
+    Walk, // walking to the next waypoint
+}
+
+#[derive(Debug, Clone)]
+pub struct AiState {
+    pub home: Vector2<f32>, // where the character was first seen, which it returns to
+    pub mode: AiMode,
+    pub path: Vec<Vector2<f32>>, // waypoints left to walk, ex: a minion's lane
+    closest: f32, // closest the current walk has come to where it is going
+    stalled: u32, // ticks since the current walk last got closer
+}
+
+impl AiState {
+    pub fn new(home: Vector2<f32>, path: Vec<Vector2<f32>>) -> Self {
+        Self { home, mode: AiMode::Idle, path, closest: f32::MAX, stalled: 0 }
+    }
+
+    fn walk_to(&mut self, destination: Vector2<f32>) -> Option<AiAction> {
+        self.closest = f32::MAX;
+        self.stalled = 0;
+        Some(AiAction::MoveTo(destination))
+    }
+
+    // keep walking to a destination, giving the move again if the character stopped getting closer
+    // without getting there
+    fn keep_walking(&mut self, pos: &Vector2<f32>, destination: Vector2<f32>) -> Option<AiAction> {
+        let distance = (destination - pos).magnitude();
+        if distance < self.closest - STALL_PROGRESS {
+            self.closest = distance;
+            self.stalled = 0;
+        } else {
+            self.stalled += 1;
+        }
+        match self.stalled < STALL_TICKS {
+            true => None,
+            false => self.walk_to(destination),
+        }
+    }
+}
+
+pub enum AiAction {
+    Attack(CharacterID),
+    MoveTo(Vector2<f32>),
+}
+
+// decides what one type of character does
+// think runs every tick, but should only return an action when the character needs to do
+// something new, since every action is sent to the clients like player input
+pub trait Behavior {
+    fn think(&self, world: &World, cid: &CharacterID, state: &mut AiState) -> Option<AiAction>;
+}
+
+pub struct IdleBehavior;
+
+impl Behavior for IdleBehavior {
+    fn think(&self, _: &World, _: &CharacterID, _: &mut AiState) -> Option<AiAction> {
+        None
+    }
+}
+
+// attack the closest enemy that comes within the aggro radius, and give up and walk home once
+// chasing it leads too far from home
+// characters with a path walk it between fights, and their home is wherever they left it
+pub struct AggroBehavior {
+    pub aggro_radius: f32,
+    pub leash_radius: f32,
+}
+
+impl Behavior for AggroBehavior {
+    fn think(&self, world: &World, cid: &CharacterID, state: &mut AiState) -> Option<AiAction> {
+        if !is_alive(world, cid) {
+            state.mode = AiMode::Idle;
+            return None;
+        }
+        let pos = position(world, cid)?;
+        let from_home = (pos - state.home).magnitude();
+        match state.mode {
+            AiMode::Leash if from_home > HOME_RADIUS => return state.keep_walking(&pos, state.home),
+            AiMode::Leash => state.mode = AiMode::Idle,
+            AiMode::Chase(_) if from_home > self.leash_radius => {
+                state.mode = AiMode::Leash;
+                return state.walk_to(state.home);
+            },
+            AiMode::Chase(target) if is_enemy(world, cid, &target) => return None,
+            AiMode::Chase(_) => state.mode = AiMode::Idle,
+            AiMode::Idle | AiMode::Walk => (),
+        }
+        if let Some(target) = nearest_enemy(world, cid, &pos, self.aggro_radius) {
+            if !state.path.is_empty() {
+                state.home = pos;
+            }
+            state.mode = AiMode::Chase(target);
+            return Some(AiAction::Attack(target));
+        }
+        if state.path.first().map(|next| (next - pos).magnitude() <= WAYPOINT_RADIUS).unwrap_or(false) {
+            state.home = state.path.remove(0);
+            state.mode = AiMode::Idle;
+        }
+        match state.path.first().copied() {
+            Some(next) if state.mode == AiMode::Walk => state.keep_walking(&pos, next),
+            Some(next) => {
+                state.mode = AiMode::Walk;
+                state.walk_to(next)
+            },
+            None if (pos - state.home).magnitude() > HOME_RADIUS => {
+                state.mode = AiMode::Leash;
+                state.walk_to(state.home)
+            },
+            None => None,
+        }
+    }
+}
+
+// stay put and shoot whatever comes within range
+// champions that attack allied champions come first, then the current target, then minions,
+// then anything else
+pub struct TowerBehavior;
+
+impl Behavior for TowerBehavior {
+    fn think(&self, world: &World, cid: &CharacterID, _: &mut AiState) -> Option<AiAction> {
+        if !is_alive(world, cid) {
+            return None;
+        }
+        let pos = position(world, cid)?;
+        let range = effective_stat(world, cid, Stat::Range).ok()?;
+        let current = world.auto_attack.get_component(cid).ok()?.targeting.as_ref().map(|targeting| targeting.target);
+        let in_range = enemies_in_range(world, cid, &pos, range);
+        let aggressors: Vec<CharacterID> = in_range.iter()
+            .copied()
+            .filter(|other| is_champion(world, other) && attacking_allied_champion(world, cid, other))
+            .collect();
+        let target = current.filter(|current| aggressors.contains(current))
+            .or_else(|| aggressors.first().copied())
+            .or_else(|| current.filter(|current| in_range.contains(current)))
+            .or_else(|| in_range.iter().copied().find(|other| is_minion(world, other)))
+            .or_else(|| in_range.first().copied())?;
+        (current != Some(target)).then_some(AiAction::Attack(target))
+    }
+}
+
+fn is_champion(world: &World, cid: &CharacterID) -> bool {
+    world.base.get_component(cid).map(|base| base.ctype == CharacterType::IceWiz).unwrap_or(false)
+}
+
+fn is_minion(world: &World, cid: &CharacterID) -> bool {
+    world.base.get_component(cid).map(|base| base.ctype == CharacterType::CasterMinion).unwrap_or(false)
+}
+
+// whether a character is attacking, or about to attack, a champion on this character's team
+fn attacking_allied_champion(world: &World, cid: &CharacterID, other: &CharacterID) -> bool {
+    let auto_attack = match world.auto_attack.get_component(other) {
+        Ok(auto_attack) => auto_attack,
+        Err(_) => return false,
+    };
+    auto_attack.targeting.as_ref().map(|targeting| targeting.target)
+        .into_iter()
+        .chain(auto_attack.execution.as_ref().map(|execution| execution.target))
+        .any(|target| is_champion(world, &target) && are_allies(world, cid, &target))
+}
+
+fn position(world: &World, cid: &CharacterID) -> Option<Vector2<f32>> {
+    world.base.get_component(cid).ok().map(|base| Vector2::new(base.position.x, base.position.y))
+}
+
+// a character that can be attacked by this one
+pub fn is_enemy(world: &World, cid: &CharacterID, other: &CharacterID) -> bool {
+    *other != *cid
+        && is_alive(world, other)
+        && world.health.get_component(other).is_ok()
+        && world.base.get_component(other).map(|base| base.targetable).unwrap_or(false)
+        && !are_allies(world, cid, other)
+}
+
+// closest first, then lowest id
+pub fn enemies_in_range(world: &World, cid: &CharacterID, pos: &Vector2<f32>, radius: f32) -> Vec<CharacterID> {
+    world.health.get_characters().into_iter()
+        .filter(|other| is_enemy(world, cid, other))
+        .filter_map(|other| position(world, &other).map(|other_pos| ((other_pos - pos).magnitude(), other)))
+        .filter(|(dist, _)| *dist <= radius)
+        .sorted_by(|(a_dist, a), (b_dist, b)| a_dist.total_cmp(b_dist).then(a.cmp(b)))
+        .map(|(_, other)| other)
+        .collect()
+}
+
+pub fn nearest_enemy(world: &World, cid: &CharacterID, pos: &Vector2<f32>, radius: f32) -> Option<CharacterID> {
+    enemies_in_range(world, cid, pos, radius).first().copied()
+}
+
+pub struct Ai {
+    behaviors: HashMap<CharacterType, Box<dyn Behavior>>,
+    states: HashMap<CharacterID, AiState>,
+    paths: HashMap<CharacterID, (bool, Vec<Vector2<f32>>)>, // paths for characters that haven't been created yet, and whether an update has passed since
+}
+
+impl Ai {
+    pub fn new() -> Self {
+        let mut ai = Self {
+            behaviors: HashMap::new(),
+            states: HashMap::new(),
+            paths: HashMap::new(),
+        };
+        ai.set_behavior(CharacterType::CasterMinion, Box::new(AggroBehavior {
+            aggro_radius: 3.0,
+            leash_radius: 6.0,
+        }));
+        ai.set_behavior(CharacterType::Tower, Box::new(TowerBehavior));
+        ai
+    }
+
+    pub fn set_behavior(&mut self, typ: CharacterType, behavior: Box<dyn Behavior>) {
+        self.behaviors.insert(typ, behavior);
+    }
+
+    // give a character waypoints to walk, which can be done before the character is created
+    pub fn set_path(&mut self, cid: CharacterID, path: Vec<Vector2<f32>>) {
+        match self.states.get_mut(&cid) {
+            Some(state) => {
+                state.path = path;
+                state.mode = AiMode::Idle;
+            },
+            None => {
+                self.paths.insert(cid, (false, path));
+            },
+        }
+    }
+
+    // commands for every character with a behavior, except the ones players control
+    pub fn update(&mut self, world: &World, id_gen: &mut CharacterIDGenerator, controlled: &[CharacterID]) -> Vec<WorldCommand> {
+        self.states.retain(|cid, _| world.characters.contains(cid));
+        // characters are created the tick their path is set, so a path still waiting after that
+        // belongs to a character that failed to be created
+        self.paths.retain(|cid, (waited, _)| world.characters.contains(cid) || !*waited);
+        for (waited, _) in self.paths.values_mut() {
+            *waited = true;
+        }
+        let mut characters: Vec<CharacterID> = world.characters.iter().copied().collect();
+        characters.sort();
+        let mut commands = vec![];
+        for cid in characters {
+            if controlled.contains(&cid) {
+                continue;
+            }
+            let behavior = match world.base.get_component(&cid).ok().and_then(|base| self.behaviors.get(&base.ctype)) {
+                Some(behavior) => behavior,
+                None => continue,
+            };
+            let state = match self.states.get_mut(&cid) {
+                Some(state) => state,
+                None => match position(world, &cid) {
+                    Some(home) => self.states.entry(cid).or_insert(AiState::new(home, self.paths.remove(&cid).map(|(_, path)| path).unwrap_or_default())),
+                    None => continue,
+                },
+            };
+            commands.extend(behavior.think(world, &cid, state).map(|action| match action {
+                AiAction::Attack(target) => WorldCommand::CharacterComponent(
+                    cid,
+                    ComponentID::AutoAttack,
+                    CharacterCommand::AutoAttack(AutoAttackCommand {
+                        target,
+                        projectile_gen_ids: id_gen.generate_range(ATTACK_ID_COUNT),
+                    })
+                ),
+                AiAction::MoveTo(destination) => WorldCommand::CharacterComponent(
+                    cid,
+                    ComponentID::Movement,
+                    CharacterCommand::Movement(MoveCharacter { destination })
+                ),
+            }));
+        }
+        commands
+    }
+}
+
+impl Default for Ai {
+    fn default() -> Self {
+        Self::new()
+    }
+}
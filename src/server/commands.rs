@@ -61,6 +61,7 @@ pub fn execute_server_command(command: &[u8], context: ((Protocol, &SocketAddr),
             PlayerLogOut => drun::<crate::model::player::commands::PlayerLogOut>(data, context),
             GetPlayerData => drun::<crate::model::player::commands::GetPlayerData>(data, context),
             PlayerSubs => drun::<crate::model::player::commands::PlayerSubs>(data, context),
+            ReadyRequest => drun::<crate::model::lobby::commands::ReadyRequest>(data, context),
             GenerateCharacter => drun::<crate::model::world::commands::GenerateCharacter>(data, context),
             ListChar => drun::<crate::model::world::commands::ListChar>(data, context),
             // UpdateCharacter => drun::<crate::model::world::commands::UpdateCharacter>(data, context),
@@ -68,7 +69,13 @@ pub fn execute_server_command(command: &[u8], context: ((Protocol, &SocketAddr),
             MoveCharacterRequest => drun::<crate::model::world::system::movement::MoveCharacterRequest>(data, context),
             AutoAttackRequest => drun::<crate::model::world::system::auto_attack::AutoAttackRequest>(data, context),
             FlashRequest => drun::<crate::model::world::system::flash::FlashRequest>(data, context),
+            AoeRequest => drun::<crate::model::world::system::aoe::AoeRequest>(data, context),
+            BoltRequest => drun::<crate::model::world::system::bolt::BoltRequest>(data, context),
+            BuyRequest => drun::<crate::model::world::system::inventory::BuyRequest>(data, context),
+            SellRequest => drun::<crate::model::world::system::inventory::SellRequest>(data, context),
             EnsureCharacter => drun::<crate::model::world::commands::EnsureCharacter>(data, context),
+            FixWorldAck => drun::<crate::model::world::commands::FixWorldAck>(data, context),
+            FixWorldNack => drun::<crate::model::world::commands::FixWorldNack>(data, context),
             ClearWorld => drun::<crate::model::world::commands::ClearWorld>(data, context),
             _ => {
                 println!("Command ID not implemented on server: {:?}", id);
@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+use nalgebra::Vector2;
+use crate::model::world::{World, character::CharacterID};
+
+// width of a grid cell in world units
+const CELL_SIZE: f32 = 2.0;
+
+// how many cells around the focus cell, in each direction, a player is interested in
+const INTEREST_RANGE: i32 = 3;
+
+// the most characters a player is sent, so that a client's updates stay bounded however many
+// characters the world has
+pub const MAX_INTEREST: usize = 48;
+
+// characters bucketed by the grid cell they stand in, rebuilt every tick
+pub struct InterestGrid {
+    cells: HashMap<Vector2<i32>, Vec<(CharacterID, Vector2<f32>)>>,
+}
+
+impl InterestGrid {
+    pub fn new(world: &World) -> Self {
+        let mut cells: HashMap<Vector2<i32>, Vec<(CharacterID, Vector2<f32>)>> = HashMap::new();
+        for (cid, base) in &world.base.components {
+            if world.characters.contains(cid) {
+                let position = Vector2::new(base.position.x, base.position.y);
+                cells.entry(Self::cell(&position)).or_default().push((*cid, position));
+            }
+        }
+        Self { cells }
+    }
+
+    fn cell(position: &Vector2<f32>) -> Vector2<i32> {
+        Vector2::new((position.x / CELL_SIZE).floor() as i32, (position.y / CELL_SIZE).floor() as i32)
+    }
+
+    // characters in the cells around a point, nearest first
+    pub fn around(&self, focus: &Vector2<f32>) -> Vec<CharacterID> {
+        let center = Self::cell(focus);
+        let mut characters: Vec<(f32, CharacterID)> = (-INTEREST_RANGE..=INTEREST_RANGE)
+            .flat_map(|j| (-INTEREST_RANGE..=INTEREST_RANGE).map(move |i| center + Vector2::new(i, j)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .map(|(cid, position)| ((position - focus).magnitude_squared(), *cid))
+            .collect();
+        characters.sort_by(|(a_dist, a), (b_dist, b)| a_dist.total_cmp(b_dist).then(a.cmp(b)));
+        characters.into_iter().map(|(_, cid)| cid).collect()
+    }
+}
@@ -1,30 +1,49 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
 use std::time::Duration;
+use nalgebra::Vector2;
 use crate::model::action_queue::ActionQueue;
-use crate::model::world::commands::{WorldCommand, RunWorldCommand};
+use crate::model::world::commands::{WorldCommand, RunWorldCommand, WorldStateHash, WorldSnapshot, Handshake, VisionChange, EnsureCharacter};
+use crate::model::world::definitions::{Definitions, DEFINITIONS_PATH};
 use crate::model::world::logging::Logger;
-use crate::model::world::system::collision::CollisionInfo;
+use crate::model::world::map::MapPackage;
 use crate::model::world::template::WorldTemplate;
 use crate::model::{Subscription, PrintError, TICK_RATE};
 use crate::model::commands::{GetCommandID, MakeBytes};
 use crate::model::player::commands::{ChatMessage, PlayerDataPayload, IndicateClientPlayer};
-use crate::model::player::model::{PlayerManager, PlayerManagerUpdate, PlayerDataView};
-use crate::model::world::{World, WorldError, CharacterCommandState, WorldErrorI};
+use crate::model::player::server::PlayerCommand;
+use crate::model::lobby::{commands::{LobbyStatus, MatchCountdown, MatchStarted, MatchEnded}, model::{MatchManager, MatchUpdate, MatchPlayer}};
+use crate::model::player::model::{PlayerManager, PlayerManagerUpdate, PlayerDataView, PlayerID, TeamID};
+use crate::model::world::{World, WorldError, CharacterCommandState, WorldErrorI, WorldEvent, vision};
 use crate::model::world::character::{CharacterIDGenerator, CharacterID};
 use crate::networking::Protocol;
 use crate::networking::server::{Server as Connection, ServerUpdate};
 use self::update_loop::UpdateLoop;
+use super::ai::Ai;
+use super::waves::WaveSpawner;
+use super::vision::{VisionTracker, VisionDelta, command_visible, relevant};
+use super::interest::InterestGrid;
 
 use super::commands::{SendCommands, execute_server_command};
 
 pub mod update_loop {
-    use std::time::{Duration, Instant};
-    use crate::model::{world::{World, commands::FixWorld}, commands::MakeBytes, WorldTick};
+    use std::{time::{Duration, Instant}, collections::{HashMap, HashSet, BTreeMap}};
+    use crate::model::{world::{World, commands::FixWorld, character::CharacterID, replication::{self, ComponentData}}, commands::MakeBytes, WorldTick, player::model::PlayerID};
+
+    // how many unacknowledged updates to remember per character
+    const MAX_PENDING: usize = 16;
+
+    // what each client has acknowledged, so that updates only need to contain changes
+    #[derive(Default)]
+    struct ClientBaselines {
+        acked: HashMap<CharacterID, (WorldTick, ComponentData)>,
+        pending: HashMap<CharacterID, BTreeMap<WorldTick, ComponentData>>,
+    }
 
     pub struct UpdateLoop {
         last_update: Option<Instant>,
         update_interval: Duration,
+        clients: HashMap<PlayerID, ClientBaselines>,
         pub errors: Vec<String>
     }
 
@@ -33,51 +52,102 @@ pub mod update_loop {
             UpdateLoop {
                 last_update: Some(Instant::now()),
                 update_interval: Duration::new(0, 1000 * 1000 * 1000),
+                clients: HashMap::new(),
                 errors: vec![],
             }
         }
 
-        pub fn send_next_update(&mut self, world: &World, now: Instant, tick: WorldTick, tick_ordering: &mut u32) -> Vec<Box<[u8]>> {
+        // the client has applied the update for this character at this tick
+        pub fn ack(&mut self, player: &PlayerID, id: CharacterID, tick: WorldTick) {
+            if let Some(client) = self.clients.get_mut(player) {
+                if let Some(pending) = client.pending.get_mut(&id) {
+                    if let Some(components) = pending.remove(&tick) {
+                        if client.acked.get(&id).map(|(acked, _)| *acked < tick).unwrap_or(true) {
+                            client.acked.insert(id, (tick, components));
+                        }
+                        pending.retain(|pending_tick, _| *pending_tick > tick);
+                    }
+                }
+            }
+        }
+
+        // the client lost its baseline for this character, so the next update contains all of it
+        pub fn nack(&mut self, player: &PlayerID, id: CharacterID) {
+            if let Some(client) = self.clients.get_mut(player) {
+                client.acked.remove(&id);
+                client.pending.remove(&id);
+            }
+        }
+
+        // forget everything the client acknowledged, ex: after it loaded a snapshot
+        pub fn reset_client(&mut self, player: &PlayerID) {
+            self.clients.remove(player);
+        }
+
+        // each player is only sent the characters they can see
+        pub fn send_next_update(&mut self, world: &World, players: &[(PlayerID, HashSet<CharacterID>)], now: Instant, tick: WorldTick, tick_ordering: &mut u32) -> Vec<(PlayerID, Vec<Box<[u8]>>)> {
             let should_update = match self.last_update {
                 None => true,
                 Some(time) => now - time > self.update_interval
             };
-            match should_update {
-                false => vec![],
-                true => {
-                    self.last_update = Some(now);
-                    let x: Vec<Box<[u8]>> = world.characters.iter()
-                        .filter_map(|cid| world.make_cmd_update_character(*cid))
-                        .filter_map(|cmd| match
-                                    (&FixWorld {
-                                        update: cmd,
-                                        tick,
-                                        ordering: {
-                                            let x = *tick_ordering;
-                                            *tick_ordering += 1;
-                                            x
-                                        },
-                                    })
-                                    // (&RunWorldCommand {
-                                    //     command: WorldCommand::Update(cmd),
-                                    //     tick,
-                                    //     ordering: {
-                                    //         let x = *tick_ordering;
-                                    //         *tick_ordering += 1;
-                                    //         x
-                                    //     }
-                                    // })
-                        .make_bytes() {
-                            Ok(bytes) => Some(bytes),
-                            Err(err) => {
-                                self.errors.push(format!("Error serializing update command: {}", err));
-                                None
-                            }
-                        })
-                        .collect();
-                    x
+            if !should_update {
+                return vec![];
+            }
+            self.last_update = Some(now);
+            self.clients.retain(|player, _| players.iter().any(|(other, _)| other == player));
+
+            let mut characters: Vec<CharacterID> = world.characters.iter().copied().collect();
+            characters.sort();
+            let current: Vec<(CharacterID, ComponentData)> = characters.into_iter()
+                .filter_map(|cid| world.make_cmd_update_character(cid))
+                .map(|update| (update.id, update.components))
+                .collect();
+
+            let mut updates = vec![];
+            for (player, visible) in players {
+                let client = self.clients.entry(*player).or_default();
+                // characters out of vision were erased on the client, so their baselines are gone too
+                client.acked.retain(|cid, _| visible.contains(cid));
+                client.pending.retain(|cid, _| visible.contains(cid));
+                let mut messages = vec![];
+                for (id, components) in current.iter().filter(|(id, _)| visible.contains(id)) {
+                    let baseline = client.acked.get(id);
+                    let (changed, removed) = match replication::make_delta(components, baseline.map(|(_, data)| data)) {
+                        Ok(delta) => delta,
+                        Err(err) => {
+                            self.errors.push(format!("Error packing update for {:?}: {}", id, err));
+                            continue
+                        }
+                    };
+                    if changed.is_empty() && removed.is_empty() {
+                        // the client's baseline is already up to date
+                        continue;
+                    }
+                    let pending = client.pending.entry(*id).or_default();
+                    pending.insert(tick, components.clone());
+                    while pending.len() > MAX_PENDING {
+                        pending.pop_first();
+                    }
+                    let fix = FixWorld {
+                        id: *id,
+                        baseline: baseline.map(|(baseline_tick, _)| *baseline_tick),
+                        changed,
+                        removed,
+                        tick,
+                        ordering: {
+                            let x = *tick_ordering;
+                            *tick_ordering += 1;
+                            x
+                        },
+                    };
+                    match fix.make_bytes() {
+                        Ok(bytes) => messages.push(bytes),
+                        Err(err) => self.errors.push(format!("Error serializing update command: {}", err)),
+                    }
                 }
+                updates.push((*player, messages));
             }
+            updates
         }
     }
 }
@@ -93,16 +163,34 @@ pub struct Server {
     pub world_commands: Vec<WorldCommand>,
     pub action_queues: HashMap<CharacterID, ActionQueue>,
     pub world_template: WorldTemplate,
+    pub update_loop: UpdateLoop,
+    pub definitions_hash: u64,
+    pub map: MapPackage,
+    pub ai: Ai,
+    pub waves: WaveSpawner,
+    pub vision: VisionTracker,
+    pub matches: MatchManager,
 }
 
 
 impl Server {
-    pub fn run(ports: (u16, u16)) -> Result<(), std::io::Error> {
+    pub fn run(ports: (u16, u16), map_id: &str) -> Result<(), std::io::Error> {
         let mut server = {
-            let world = World::new(CollisionInfo::test_collision());
+            let definitions = Definitions::load(DEFINITIONS_PATH).map_err(|err| std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Failed to load character definitions: {:?}", err)
+            ))?;
+            let map = MapPackage::load(map_id).map_err(|err| std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Failed to load map {}: {:?}", map_id, err)
+            ))?;
+            println!("Running map {} ({})", map.manifest.name, map.id);
+            let world = World::new(&map, &definitions);
             Server {
+                definitions_hash: definitions.hash,
                 stop: false,
                 world_template: WorldTemplate { world: world.clone() },
+                update_loop: UpdateLoop::init(&world),
                 world,
                 character_id_gen: CharacterIDGenerator::new(),
                 player_manager: PlayerManager::new(),
@@ -110,9 +198,13 @@ impl Server {
                 tick_ordering: 0,
                 world_commands: vec![],
                 action_queues: Default::default(),
+                ai: Ai::new(),
+                waves: WaveSpawner::new(&map.manifest),
+                map,
+                vision: VisionTracker::new(),
+                matches: MatchManager::new(),
             }
         };
-        let mut update_loop = UpdateLoop::init(&server.world);
 
         // match test::make_attack_circle(10, 10.0, server.character_id_gen.generate_range(100000), &mut server.world) {
         //     Ok(()) => (),
@@ -172,6 +264,11 @@ impl Server {
 
             for addr in connects {
                 println!("Connection from {}", addr);
+                server.connection.send(Protocol::TCP, &addr, &Handshake {
+                    definitions_hash: server.definitions_hash,
+                    map: server.map.id.clone(),
+                    map_hash: server.map.hash,
+                }).print();
             }
             for addr in disconnects {
                 println!("Disconnect from {}", addr);
@@ -192,8 +289,27 @@ impl Server {
                 let delta_time = 1.0 / TICK_RATE;
                 tick_timer -= delta_time;
 
-                let update_data = update_loop.send_next_update(&server.world, current_time, server.world.tick, &mut server.tick_ordering);
-                server.broadcast_data(Subscription::World, Protocol::UDP, &update_data);
+                let players = server.world_subscribers();
+                // everything sent this tick is filtered by what each player's team can see near them
+                let player_vision = server.update_vision(&players);
+                let updates = server.update_loop.send_next_update(&server.world, &player_vision, current_time, server.world.tick, &mut server.tick_ordering);
+                // everything a client runs for a tick goes over TCP, so that it arrives in order
+                for (player, messages) in updates {
+                    if let Some(addr) = server.player_manager.get_player_connection(&player) {
+                        for message in messages {
+                            match server.connection.send_data(Protocol::TCP, &addr, message) {
+                                Ok(()) => (), Err(err) => println!("Error sending TCP message to {}: {}", addr, err)
+                            }
+                        }
+                    }
+                }
+
+                // npcs decide what to do before this tick's commands are collected
+                // waves only spawn while a match is being played
+                if server.matches.in_progress() {
+                    server.run_waves();
+                }
+                server.run_ai();
 
                 let mut t_o = server.tick_ordering;
                 let mut commands = server.world_commands.clone();
@@ -222,7 +338,7 @@ impl Server {
                 }
 
                 for command in &commands {
-                    server.broadcast(Subscription::World, Protocol::UDP, &RunWorldCommand {
+                    let run = RunWorldCommand {
                         command: command.clone(),
                         tick: server.world.tick,
                         ordering: {
@@ -230,22 +346,44 @@ impl Server {
                             t_o += 1;
                             ordering
                         },
-                    });
+                    };
+                    for (player, visible) in &player_vision {
+                        if command_visible(command, visible) {
+                            server.send_player(player, Protocol::TCP, &run);
+                        }
+                    }
                 }
                 server.tick_ordering = t_o;
+                for (player, _) in &player_vision {
+                    if let Some(hash) = server.vision.state_hash(player) {
+                        let hash = WorldStateHash {
+                            tick: server.world.tick,
+                            hash,
+                        };
+                        server.send_player(player, Protocol::TCP, &hash);
+                    }
+                }
+                server.vision.run(&commands, delta_time);
                 server.world = server.world.update(&commands, delta_time);
                 logger.log(&server.world);
+                for event in server.world.events.clone() {
+                    match event {
+                        WorldEvent::Kill { target, killer } => server.announce_kill(&target, killer.as_ref()),
+                        WorldEvent::Damage(_) => (),
+                    }
+                }
                 for error in server.world.errors.drain(0..server.world.errors.len()) {
                     match error {
                         WorldError(WorldErrorI::Info(_st)) => (), //println!("Tick {}, {}", server.world.tick, st),
                         _ => println!("Server world error: {:?}", error),
                     }
                 }
+                server.run_match(delta_time);
 
                 server.tick_ordering = 0;
             }
 
-            for error in update_loop.errors.drain(0..update_loop.errors.len()) {
+            for error in server.update_loop.errors.drain(0..server.update_loop.errors.len()) {
                 println!("Update loop error: {}", error);
             }
 
@@ -254,6 +392,27 @@ impl Server {
         Ok(())
     }
 
+    fn character_owner_name(&self, cid: &CharacterID) -> Option<String> {
+        self.player_manager.all_player_ids().iter()
+            .filter_map(|id| self.player_manager.get_player(id))
+            .find(|player| player.selected_char == Some(*cid))
+            .map(|player| player.name.clone())
+    }
+
+    // only deaths of player characters are worth a chat message
+    fn announce_kill(&mut self, target: &CharacterID, killer: Option<&CharacterID>) {
+        if let Some(name) = self.character_owner_name(target) {
+            // players by name, anything else by its type, ex: towers
+            let message = match killer.map(|killer| (killer, self.character_owner_name(killer)
+                .or_else(|| self.world.base.components.get(killer).map(|base| base.ctype.describe().to_string())))) {
+                Some((killer, _)) if killer == target => format!("{} killed themselves.", name),
+                Some((_, Some(killer))) => format!("{} was killed by {}.", name, killer),
+                _ => format!("{} died.", name),
+            };
+            self.broadcast(Subscription::Chat, Protocol::TCP, &ChatMessage(message));
+        }
+    }
+
     pub fn broadcast<T>(&mut self, sub: Subscription, protocol: Protocol, message: &T) where T: GetCommandID {
         match message.make_bytes() {
             Ok(bytes) => self.broadcast_data(sub, protocol, &vec![bytes]),
@@ -280,6 +439,173 @@ impl Server {
         }
     }
 
+    // send the world so the client can start its history from the current tick
+    pub fn send_world_snapshot(&mut self, addr: &SocketAddr) {
+        let player = self.player_manager.get_connected_player(addr);
+        let visible = match player.and_then(|player| self.player_team(&player).map(|team| (player, team))) {
+            Some((player, team)) => relevant(
+                &InterestGrid::new(&self.world).around(&self.interest_focus(&player)),
+                &vision::team_vision(&self.world, team)),
+            None => HashSet::new(),
+        };
+        let snapshot = WorldSnapshot::new(&self.world, &self.character_id_gen, &visible);
+        self.connection.send(Protocol::TCP, addr, &snapshot).print();
+        // the client drops its baselines when it loads the snapshot
+        if let Some(player) = player {
+            self.update_loop.reset_client(&player);
+            match snapshot.make_world(&World::from(&self.world_template)) {
+                Ok(world) => self.vision.set(player, world),
+                Err(err) => println!("Error loading snapshot for {}: {:?}", player, err),
+            }
+        }
+    }
+
+    pub fn player_team(&self, player: &PlayerID) -> Option<TeamID> {
+        self.player_manager.get_player(player).and_then(|player| player.team)
+    }
+
+    pub fn send_player<T>(&mut self, player: &PlayerID, protocol: Protocol, message: &T) where T: GetCommandID {
+        if let Some(addr) = self.player_manager.get_player_connection(player) {
+            match self.connection.send(protocol, &addr, message) {
+                Ok(()) => (), Err(err) => println!("Error sending {} message to {}: {}", protocol, addr, err)
+            }
+        }
+    }
+
+    // players are interested in the area around their character, or the middle of the map when they
+    // don't have one
+    fn interest_focus(&self, player: &PlayerID) -> Vector2<f32> {
+        self.player_manager.get_player(player)
+            .and_then(|player| player.selected_char)
+            .and_then(|cid| self.world.base.components.get(&cid))
+            .map(|base| Vector2::new(base.position.x, base.position.y))
+            .unwrap_or_else(Vector2::zeros)
+    }
+
+    // tell each player which characters came into and went out of their team's vision and their
+    // area of interest, or were changed by something they weren't sent, then return what every
+    // player is sent
+    pub fn update_vision(&mut self, players: &[PlayerID]) -> Vec<(PlayerID, HashSet<CharacterID>)> {
+        let grid = InterestGrid::new(&self.world);
+        let teams: Vec<(PlayerID, Option<TeamID>, Vec<CharacterID>)> = players.iter()
+            .map(|player| (*player, self.player_team(player), grid.around(&self.interest_focus(player))))
+            .collect();
+        for VisionDelta { player, updated, left } in self.vision.update(&self.world, &teams) {
+            let change = VisionChange {
+                tick: self.world.tick,
+                ordering: {
+                    let ordering = self.tick_ordering;
+                    self.tick_ordering += 1;
+                    ordering
+                },
+                updated,
+                left,
+            };
+            // sent reliably, the client's world is only right if it gets every one of these
+            self.send_player(&player, Protocol::TCP, &change);
+        }
+        players.iter().map(|player| (*player, self.vision.get(player))).collect()
+    }
+
+    // connected players subscribed to the world
+    pub fn world_subscribers(&self) -> Vec<PlayerID> {
+        self.player_manager.all_player_ids().iter().copied()
+            .filter(|id| self.player_manager.get_player_connection(id).is_some())
+            .filter(|id| self.player_manager.get_player_subscriptions(id)
+                .map(|subs| subs.contains(&Subscription::World))
+                .unwrap_or(false))
+            .collect()
+    }
+
+    // logged in players, who take part in the match
+    fn match_players(&self) -> Vec<MatchPlayer> {
+        self.player_manager.all_player_ids().iter()
+            .filter(|id| self.player_manager.get_player_connection(id).is_some())
+            .filter_map(|id| self.player_manager.get_player(id))
+            .map(|player| (player.id, player.team, player.selected_char))
+            .collect()
+    }
+
+    pub fn broadcast_lobby_status(&mut self) {
+        let status = LobbyStatus {
+            players: self.match_players().into_iter().map(|(player, _, _)| player).collect(),
+            ready: self.matches.ready_players(),
+        };
+        self.broadcast(Subscription::Chat, Protocol::TCP, &status);
+    }
+
+    pub fn run_match(&mut self, delta_time: f32) {
+        let players = self.match_players();
+        for update in self.matches.update(delta_time, &self.world, &players) {
+            match update {
+                MatchUpdate::Countdown(seconds) => self.broadcast(Subscription::Chat, Protocol::TCP, &MatchCountdown(Some(seconds))),
+                MatchUpdate::CountdownCancelled => {
+                    self.broadcast(Subscription::Chat, Protocol::TCP, &MatchCountdown(None));
+                    self.broadcast_lobby_status();
+                },
+                MatchUpdate::Start => self.start_match(),
+                MatchUpdate::End(summary) => self.broadcast(Subscription::Chat, Protocol::TCP, &MatchEnded(summary)),
+                MatchUpdate::Reset => {
+                    self.reset_world();
+                    self.broadcast_lobby_status();
+                },
+            }
+        }
+    }
+
+    // every match is played on a fresh copy of the template, with a champion for each player
+    fn start_match(&mut self) {
+        self.reset_world();
+        for (player, _, _) in self.match_players() {
+            if let Some(addr) = self.player_manager.get_player_connection(&player) {
+                EnsureCharacter.run(&addr, &player, self);
+            }
+        }
+        self.broadcast(Subscription::Chat, Protocol::TCP, &MatchStarted);
+    }
+
+    // start over from the template, keeping the tick so that clients don't lose track of time
+    pub fn reset_world(&mut self) {
+        let tick = self.world.tick;
+        self.world = World::from(&self.world_template);
+        self.world.tick = tick;
+        self.waves.reset(tick);
+        self.vision.reset();
+        self.world_commands.clear();
+        self.action_queues.clear();
+        for id in self.player_manager.all_player_ids().iter() {
+            if let Some(player) = self.player_manager.get_player_mut(id) {
+                player.selected_char = None;
+            }
+        }
+        self.broadcast(Subscription::Chat, Protocol::TCP, &PlayerDataPayload(self.player_manager.get_view()));
+        for player in self.world_subscribers() {
+            if let Some(addr) = self.player_manager.get_player_connection(&player) {
+                self.send_world_snapshot(&addr);
+            }
+        }
+    }
+
+    pub fn run_waves(&mut self) {
+        let teams = self.player_manager.team_ids();
+        let commands = self.waves.update(self.world.tick, &teams, &mut self.character_id_gen, &mut self.ai);
+        for command in commands {
+            self.run_world_command(None, command);
+        }
+    }
+
+    // run the commands of every character that no player controls
+    pub fn run_ai(&mut self) {
+        let controlled: Vec<CharacterID> = self.player_manager.all_player_ids().iter()
+            .filter_map(|id| self.player_manager.get_player(id))
+            .filter_map(|player| player.selected_char)
+            .collect();
+        let commands = self.ai.update(&self.world, &mut self.character_id_gen, &controlled);
+        for command in commands {
+            self.run_world_command(None, command);
+        }
+    }
+
     pub fn run_world_command(&mut self, addr: Option<&SocketAddr>, command: WorldCommand) {
         let res = self.world.validate_command(&command);
         if let Ok(res) = res {
@@ -1,2 +1,6 @@
 pub mod commands;
 pub mod main;
+pub mod ai;
+pub mod waves;
+pub mod vision;
+pub mod interest;
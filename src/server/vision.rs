@@ -0,0 +1,176 @@
+use std::collections::{HashMap, HashSet};
+use itertools::Itertools;
+use crate::model::{player::model::{PlayerID, TeamID}, world::{World, character::CharacterID, commands::{WorldCommand, GlobalCommand, UpdateCharacter, apply_vision_change}, vision}};
+
+use super::interest::MAX_INTEREST;
+
+// what a player's client has to change at the start of a tick to match the server on what it is sent
+pub struct VisionDelta {
+    pub player: PlayerID,
+    pub updated: Vec<UpdateCharacter>,
+    pub left: Vec<CharacterID>,
+}
+
+// a copy of each player's client world, run on the same commands the client is sent
+// clients only simulate what they are sent, so anything they aren't sent can change what they are,
+// ex: a hidden enemy attacking a visible ally. comparing the copy against the server's world finds
+// those characters, so they can be sent again before the client's world drifts
+#[derive(Default)]
+pub struct VisionTracker {
+    clients: HashMap<PlayerID, World>,
+}
+
+impl VisionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the characters the player's client has, which is what it was last sent
+    pub fn get(&self, player: &PlayerID) -> HashSet<CharacterID> {
+        self.clients.get(player).map(|client| client.characters.clone()).unwrap_or_default()
+    }
+
+    // the player's client just started over from this world, ex: a world snapshot
+    pub fn set(&mut self, player: PlayerID, world: World) {
+        self.clients.insert(player, world);
+    }
+
+    // everyone starts over with an empty world
+    pub fn reset(&mut self) {
+        self.clients.clear();
+    }
+
+    // the hash the player's client should have for its whole world at the start of this tick
+    pub fn state_hash(&self, player: &PlayerID) -> Option<u64> {
+        self.clients.get(player).map(|client| client.state_hash())
+    }
+
+    // interest lists the characters around each player, nearest first
+    // players that haven't been sent a world yet are skipped
+    pub fn update(&mut self, world: &World, players: &[(PlayerID, Option<TeamID>, Vec<CharacterID>)]) -> Vec<VisionDelta> {
+        self.clients.retain(|player, _| players.iter().any(|(other, _, _)| other == player));
+        // teammates share vision, so it only needs to be found once per team
+        let mut teams: HashMap<TeamID, HashSet<CharacterID>> = HashMap::new();
+        let mut deltas = vec![];
+        for (player, team, interest) in players {
+            let now: HashSet<CharacterID> = match team {
+                Some(team) => {
+                    let seen = teams.entry(*team).or_insert_with(|| vision::team_vision(world, *team));
+                    relevant(interest, seen)
+                },
+                None => HashSet::new(),
+            };
+            if let Some(delta) = self.sync(world, *player, &now) {
+                deltas.push(delta);
+            }
+        }
+        deltas
+    }
+
+    // make the player's client world match the server's on exactly the visible characters
+    pub fn sync(&mut self, world: &World, player: PlayerID, visible: &HashSet<CharacterID>) -> Option<VisionDelta> {
+        let client = self.clients.get_mut(&player)?;
+        // characters the client made itself are removed too, ex: projectiles that aren't visible
+        let left: Vec<CharacterID> = client.characters.difference(visible).copied().sorted().collect();
+        let updated: Vec<UpdateCharacter> = visible.iter()
+            .filter(|cid| !client.same_character(world, cid))
+            .sorted()
+            .filter_map(|cid| world.make_cmd_update_character(*cid))
+            .collect();
+        if updated.is_empty() && left.is_empty() {
+            return None;
+        }
+        apply_vision_change(client, &updated, &left);
+        Some(VisionDelta { player, updated, left })
+    }
+
+    // run each client world on the commands its player was sent this tick
+    pub fn run(&mut self, commands: &[WorldCommand], delta_time: f32) {
+        for client in self.clients.values_mut() {
+            let visible = client.characters.clone();
+            let sent: Vec<WorldCommand> = commands.iter()
+                .filter(|command| command_visible(command, &visible))
+                .cloned()
+                .collect();
+            *client = client.update(&sent, delta_time);
+            // the server world reports these already
+            client.errors.clear();
+        }
+    }
+}
+
+// the characters a player is sent: the nearest ones in their area of interest that their team can see
+pub fn relevant(interest: &[CharacterID], seen: &HashSet<CharacterID>) -> HashSet<CharacterID> {
+    interest.iter().filter(|cid| seen.contains(cid)).take(MAX_INTEREST).copied().collect()
+}
+
+// whether a player should be sent a world command, given what they were sent
+pub fn command_visible(command: &WorldCommand, visible: &HashSet<CharacterID>) -> bool {
+    match command {
+        WorldCommand::CharacterComponent(cid, _, _) => visible.contains(cid),
+        // new characters reach clients with their full state once they enter what the client is sent,
+        // even their own team's, which may be outside the client's area of interest
+        WorldCommand::World(GlobalCommand::CreateCharacter(..)) => false,
+        WorldCommand::World(GlobalCommand::Clear) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use nalgebra::Vector2;
+    use crate::model::{TICK_RATE, player::model::{PlayerIDGenerator, TeamIDGenerator}, world::{
+        World,
+        character::{CharacterIDGenerator, CharacterType},
+        commands::{WorldCommand, GlobalCommand, CharacterCommand, WorldSnapshot, apply_vision_change},
+        component::{ComponentID, ComponentStorageContainer},
+        definitions::{Definitions, DEFINITIONS_PATH},
+        map::{MapPackage, DEFAULT_MAP},
+        system::auto_attack::AutoAttackCommand,
+    }};
+    use super::{VisionTracker, command_visible};
+
+    // the client is never sent the attack, so only the server can tell it about the damage
+    #[test]
+    fn hidden_attacker_does_not_desync_visible_champion() {
+        let map = MapPackage::load(DEFAULT_MAP).unwrap();
+        let definitions = Definitions::load(DEFINITIONS_PATH).unwrap();
+        let template = World::new(&map, &definitions);
+        let mut ids = CharacterIDGenerator::new();
+        let mut teams = TeamIDGenerator::new();
+        let (blue, red) = (teams.generate(), teams.generate());
+        let (champion, attacker) = (ids.generate(), ids.generate());
+        let delta_time = 1.0 / TICK_RATE;
+        let mut world = template.update(&[
+            WorldCommand::World(GlobalCommand::CreateCharacter(champion, CharacterType::IceWiz, Some(blue), Vector2::new(0.0, 0.0))),
+            WorldCommand::World(GlobalCommand::CreateCharacter(attacker, CharacterType::IceWiz, Some(red), Vector2::new(0.8, 0.0))),
+        ], delta_time);
+
+        // the player's team can only see its own champion
+        let player = PlayerIDGenerator::new().generate();
+        let visible = HashSet::from([champion]);
+        let mut client = WorldSnapshot::new(&world, &ids, &visible).make_world(&template).unwrap();
+        let mut tracker = VisionTracker::new();
+        tracker.set(player, client.clone());
+
+        let mut commands = vec![WorldCommand::CharacterComponent(attacker, ComponentID::AutoAttack,
+            CharacterCommand::AutoAttack(AutoAttackCommand { target: champion, projectile_gen_ids: ids.generate_range(100) }))];
+        for _ in 0..(5.0 * TICK_RATE) as i32 {
+            if let Some(delta) = tracker.sync(&world, player, &visible) {
+                apply_vision_change(&mut client, &delta.updated, &delta.left);
+            }
+            assert!(client.same_character(&world, &champion));
+            assert_eq!(tracker.state_hash(&player), Some(client.state_hash()));
+            let sent: Vec<WorldCommand> = commands.iter()
+                .filter(|command| command_visible(command, &visible))
+                .cloned()
+                .collect();
+            client = client.update(&sent, delta_time);
+            tracker.run(&commands, delta_time);
+            world = world.update(&commands, delta_time);
+            commands.clear();
+        }
+        let health = world.health.get_component(&champion).unwrap();
+        assert!(health.health < health.max_health, "the attack never landed");
+    }
+}
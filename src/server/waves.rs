@@ -0,0 +1,77 @@
+use nalgebra::Vector2;
+use crate::model::{WorldTick, TICK_RATE, player::model::TeamID, world::{character::{CharacterType, CharacterIDGenerator}, commands::{WorldCommand, GlobalCommand}, map::{MapManifest, WaveSchedule, Lane, TowerPlacement}}};
+
+use super::ai::Ai;
+
+pub struct WaveSpawner {
+    pub schedule: WaveSchedule,
+    pub lanes: Vec<Lane>,
+    pub towers: Vec<TowerPlacement>,
+    next_wave: WorldTick,
+    towers_placed: bool,
+}
+
+impl WaveSpawner {
+    pub fn new(map: &MapManifest) -> Self {
+        let next_wave = (map.waves.first_wave * TICK_RATE).ceil() as WorldTick;
+        Self {
+            schedule: map.waves.clone(),
+            lanes: map.lanes.clone(),
+            towers: map.towers.clone(),
+            next_wave,
+            towers_placed: false,
+        }
+    }
+
+    // start the timer over from this tick, ex: when the world is reset
+    pub fn reset(&mut self, tick: WorldTick) {
+        self.next_wave = tick + (self.schedule.first_wave * TICK_RATE).ceil() as WorldTick;
+        self.towers_placed = false;
+    }
+
+    // the commands that create the towers, the first time this is called
+    fn place_towers(&mut self, teams: &[TeamID], id_gen: &mut CharacterIDGenerator) -> Vec<WorldCommand> {
+        if self.towers_placed {
+            return vec![];
+        }
+        self.towers_placed = true;
+        self.towers.iter()
+            .filter_map(|tower| teams.get(tower.side).map(|team| WorldCommand::World(
+                GlobalCommand::CreateCharacter(id_gen.generate(), CharacterType::Tower, Some(*team), tower.position))))
+            .collect()
+    }
+
+    // the commands that create a wave for each team on each lane, if one is due
+    // every member is handed its lane so the ai walks it once the member exists
+    pub fn update(&mut self, tick: WorldTick, teams: &[TeamID], id_gen: &mut CharacterIDGenerator, ai: &mut Ai) -> Vec<WorldCommand> {
+        let mut commands = self.place_towers(teams, id_gen);
+        if tick < self.next_wave {
+            return commands;
+        }
+        self.next_wave = tick + (self.schedule.interval * TICK_RATE).ceil() as WorldTick;
+        for lane in &self.lanes {
+            // only the first two teams have a side of the lane
+            for (side, team) in teams.iter().take(2).enumerate() {
+                let path: Vec<Vector2<f32>> = match side {
+                    0 => lane.waypoints.clone(),
+                    _ => lane.waypoints.iter().rev().copied().collect(),
+                };
+                // members line up behind the start of the lane
+                let start = path[0];
+                let back = match path.get(1) {
+                    Some(next) if *next != start => (start - next).normalize(),
+                    _ => Vector2::zeros(),
+                };
+                let members = self.schedule.wave.iter()
+                    .flat_map(|member| (0..member.count).map(move |_| member.ctype));
+                for (i, ctype) in members.enumerate() {
+                    let cid = id_gen.generate();
+                    let position = start + back * self.schedule.spacing * i as f32;
+                    commands.push(WorldCommand::World(GlobalCommand::CreateCharacter(cid, ctype, Some(*team), position)));
+                    ai.set_path(cid, path[1..].to_vec());
+                }
+            }
+        }
+        commands
+    }
+}